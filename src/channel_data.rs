@@ -1,6 +1,24 @@
+use chrono::DateTime;
+use std::time::Instant;
+use std::collections::HashMap;
+use chrono::prelude::Utc;
+use crate::verify::VerificationResult;
 
 pub enum ChannelData {
-    WriteLatency(f64),
-    ReadLatency(f64),
-    WriteDuration(f64)
+    StartTime(DateTime<Utc>),
+    // When create_topic returned, time retention counts from there
+    StreamCreated(Instant),
+    // Producer, latency from the actual send, latency from the intended send, send time, event bytes
+    WriteLatency(usize, f64, f64, DateTime<Utc>, u64),
+    ReadLatency(usize, f64, DateTime<Utc>, u64),
+    WriteDuration(f64),
+    ProducerDuration(usize, f64),
+    ConsumerDuration(usize, f64),
+    SubscriptionLag(usize, i64),
+    EndToEndLatency(f64),
+    // When the segments were polled and how many the stream had
+    SegmentCount(DateTime<Utc>, usize),
+    // Reader group, benchmark events a reader read from every segment
+    SegmentEvents(usize, HashMap<String, u64>),
+    Verification(VerificationResult)
 }
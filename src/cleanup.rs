@@ -0,0 +1,73 @@
+use chrono::prelude::Utc;
+use crate::config::Config;
+use crate::driver::Driver;
+
+// Deletes the stream of the run, and its scope when the run created it.
+pub fn cleanup_run(conf: &Config, driver: &dyn Driver) {
+    match driver.delete_topic() {
+        Ok(_)  => println!("\t i Deleted stream {}/{}", conf.scope, conf.stream),
+        Err(e) => println!("\t i Error at deleting stream {}/{}: {}", conf.scope, conf.stream, e),
+    }
+}
+
+// Digits of a Unix timestamp in seconds from 2001 to 2286.
+const TIMESTAMP_DIGITS: usize = 10;
+
+/*
+ * Creation time of a scope generated by the benchmark: the prefix followed by
+ * a 10 digit Unix timestamp that is not in the future. Hand-named scopes like
+ * `scope1` never match.
+ */
+fn generated_at(scope: &str, prefix: &str, now: i64) -> Option<i64> {
+    let suffix = scope.strip_prefix(prefix)?;
+    if suffix.len() != TIMESTAMP_DIGITS || !suffix.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    suffix.parse::<i64>().ok().filter(|&created| created <= now)
+}
+
+// Deletes the scopes left behind by earlier runs, scopes with other names are never touched.
+pub fn cleanup_scopes(driver: &dyn Driver, prefix: &str, max_age_hours: u64, dry_run: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let now = Utc::now().timestamp();
+    let mut deleted = 0;
+    for scope in driver.list_scopes().map_err(|e| e.to_string())? {
+        let created = match generated_at(&scope, prefix, now) {
+            Some(created) => created,
+            None          => continue,
+        };
+        let age_hours = (now - created) / 3600;
+        if age_hours < max_age_hours as i64 {
+            continue;
+        }
+        if dry_run {
+            println!("\t i Would delete scope {} ({} hours old)", scope, age_hours);
+            continue;
+        }
+        match driver.delete_scope(&scope) {
+            Ok(streams) => {
+                println!("\t i Deleted scope {} ({} hours old, {} streams)", scope, age_hours, streams);
+                deleted += 1;
+            },
+            Err(e) => println!("\t i Error at deleting scope {}: {}", scope, e),
+        }
+    }
+    println!("\t i {} scopes deleted", deleted);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_generated_names_match() {
+        let now = 1_700_000_000;
+        assert_eq!(generated_at("scope1690000000", "scope", now), Some(1_690_000_000));
+        assert_eq!(generated_at("scope1", "scope", now), None);
+        assert_eq!(generated_at("scope-2", "scope", now), None);
+        assert_eq!(generated_at("scope+169000000", "scope", now), None);
+        assert_eq!(generated_at("scope16900000000", "scope", now), None);
+        assert_eq!(generated_at("scope1800000000", "scope", now), None);
+        assert_eq!(generated_at("other1690000000", "scope", now), None);
+    }
+}
@@ -4,57 +4,221 @@ use std::fs::File;
 use serde_yaml::{self};
 use chrono::prelude::*;
 use serde::{Deserialize, Serialize};
+use crate::header::VERIFY_HEADER_LEN;
+use crate::size::{MessageSize, SizeDistribution, SizeSampler};
+use crate::routing::KeySampler;
 
 #[derive(Deserialize)]
 struct ConfigYaml {
     pub name:                   String,
+    pub driver:                 Option<String>,
+    pub mode:                   Option<String>,
+    pub read_message_num:       Option<u32>,
     pub address:                String,
-    pub payload_file:           String,
-    pub message_num:            u32,
+    pub admin_address:          Option<String>,
+    pub tenant:                 Option<String>,
+    pub payload_file:           Option<String>,
+    pub payload_type:           Option<String>,
+    pub payload_size:           Option<u64>,
+    pub payload_count:          Option<u32>,
+    pub payload_seed:           Option<u64>,
+    pub payload_compress_ratio: Option<f64>,
+    pub payload_dir:            Option<String>,
+    pub message_size:           Option<MessageSize>,
+    pub message_num:            Option<u32>,
     pub message_warmup:         Option<u32>,
-    pub producer_rate:          u32,
+    pub duration:               Option<u64>,
+    pub warmup_duration:        Option<u64>,
+    pub producer_rate:          Option<u32>,
+    pub producers:              Option<u32>,
+    pub arrival:                Option<String>,
+    pub routing_key:            Option<String>,
+    pub routing_key_count:      Option<u32>,
+    pub routing_key_zipf_exponent: Option<f64>,
+    pub separate_clients:       Option<bool>,
+    pub consumers:              Option<u32>,
+    pub subscriptions:          Option<u32>,
     pub scope:                  Option<String>,
     pub stream:                 Option<String>,
+    pub retention_type:         Option<String>,
     pub retention_time:         Option<i64>,
+    pub retention_size:         Option<i64>,
+    pub retention_check:        Option<bool>,
+    pub retention_check_timeout: Option<u64>,
+    pub scale_type:             Option<String>,
     pub scale_target_rate:      Option<i32>,
     pub scale_factor:           Option<i32>,
     pub scale_min_num_segments: Option<i32>,
+    pub segment_poll_interval:  Option<u64>,
+    pub data_dir:               Option<String>,
+    pub file_segment_size:      Option<u64>,
+    pub file_sync:              Option<String>,
+    pub file_sync_batch:        Option<u32>,
+    pub timeseries_interval:    Option<u64>,
+    pub timeseries_csv:         Option<bool>,
+    pub report_interval:        Option<u64>,
+    pub report_file:            Option<String>,
+    pub metrics_address:        Option<String>,
+    pub verify:                 Option<bool>,
+    pub read_mode:              Option<String>,
+    pub catchup_delay:          Option<u64>,
+    pub cleanup:                Option<bool>,
+}
+
+// Fields of the workload file the cleanup command needs to reach the driver.
+#[derive(Deserialize)]
+struct CleanupYaml {
+    pub driver:        Option<String>,
+    pub address:       String,
+    pub data_dir:      Option<String>,
+    pub subscriptions: Option<u32>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Config {
     pub name:                   String,
+    pub driver:                 String,
+    // write_only, read_only or both
+    pub mode:                   String,
+    // Events read per reader group, 0 reads until the end of the stream
+    pub read_message_num:       u32,
+    // Appended to the reader group names, unique per run in read_only mode
+    pub reader_group_suffix:    String,
     pub address:                String,
+    pub admin_address:          String,
+    pub tenant:                 String,
     pub payload_file:           String,
+    pub payload_type:           String,
+    pub payload_count:          u32,
+    pub payload_seed:           u64,
+    pub payload_compress_ratio: f64,
+    pub payload_dir:            String,
+    pub payload_size:           u64,
+    // Nominal (mean) size of the events
     pub message_size:           u64,
+    pub size_distribution:      SizeDistribution,
     pub message_num:            u32,
     pub message_warmup:         u32,
+    pub duration:               u64,
+    pub warmup_duration:        u64,
     pub scope:                  String,
     pub stream:                 String,
     pub producer_rate:          u32,
+    pub producers:              u32,
+    pub arrival:                String,
+    pub routing_key:            String,
+    pub routing_key_count:      u32,
+    pub routing_key_zipf_exponent: f64,
+    pub separate_clients:       bool,
+    pub consumers:              u32,
+    pub subscriptions:          u32,
+    pub retention_type:         String,
+    // Seconds
     pub retention_time:         i64,
+    // Bytes
+    pub retention_size:         i64,
+    pub retention_check:        bool,
+    pub retention_check_timeout: u64,
+    pub scale_type:             String,
     pub scale_target_rate:      i32,
     pub scale_factor:           i32,
     pub scale_min_num_segments: i32,
+    // Milliseconds
+    pub segment_poll_interval:  u64,
+    pub data_dir:               String,
+    pub file_segment_size:      u64,
+    pub file_sync:              String,
+    pub file_sync_batch:        u32,
+    pub timeseries_interval:    u64,
+    pub timeseries_csv:         bool,
+    pub report_interval:        u64,
+    pub report_file:            String,
+    pub metrics_address:        String,
+    pub verify:                 bool,
+    pub read_mode:              String,
+    // Seconds
+    pub catchup_delay:          u64,
+    pub cleanup:                bool,
 }
 
 impl Config {
     pub fn new() -> Self {
         Config {
             name:                   "".to_string(),
+            driver:                 "pravega".to_string(),
+            mode:                   "both".to_string(),
+            read_message_num:       0,
+            reader_group_suffix:    "".to_string(),
             address:                "".to_string(),
+            admin_address:          "".to_string(),
+            tenant:                 "public".to_string(),
             payload_file:           "".to_string(),
-            message_size:           0,
+            payload_type:           "file".to_string(),
+            payload_count:          16,
+            payload_seed:           0,
+            payload_compress_ratio: 0.5,
+            payload_dir:            "".to_string(),
+            payload_size:           1024,
+            message_size:           1024,
+            size_distribution:      SizeDistribution::Fixed { size: 1024 },
             message_num:            0,
             message_warmup:         5,
+            duration:               0,
+            warmup_duration:        0,
             scope:                  "".to_string(),
             stream:                 "".to_string(),
+            retention_type:         "size".to_string(),
             retention_time:         10,
+            retention_size:         10485760,
+            retention_check:        false,
+            retention_check_timeout: 60,
             producer_rate:          0,
+            producers:              1,
+            arrival:                "uniform".to_string(),
+            routing_key:            "none".to_string(),
+            routing_key_count:      100,
+            routing_key_zipf_exponent: 1.0,
+            separate_clients:       false,
+            consumers:              1,
+            subscriptions:          1,
+            scale_type:             "ByRateInEventsPerSec".to_string(),
             scale_target_rate:      1,
             scale_factor:           0,
             scale_min_num_segments: 1,
+            segment_poll_interval:  1000,
+            data_dir:               "data".to_string(),
+            file_segment_size:      67108864,
+            file_sync:              "none".to_string(),
+            file_sync_batch:        100,
+            timeseries_interval:    1000,
+            timeseries_csv:         false,
+            report_interval:        10,
+            report_file:            "".to_string(),
+            metrics_address:        "".to_string(),
+            verify:                 false,
+            read_mode:              "tail".to_string(),
+            catchup_delay:          0,
+            cleanup:                false,
+        }
+    }
+
+    // Loads only what the cleanup command needs, so the payload and rate settings are not checked.
+    pub fn load_cleanup_from_file(file_path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let file = std::fs::File::open(file_path)?;
+        let mut conf: Config = Self::new();
+        let conf_yaml: CleanupYaml = serde_yaml::from_reader(file)?;
+
+        conf.address = conf_yaml.address;
+        if let Some(driver) = conf_yaml.driver {
+            conf.driver = driver;
         }
+        if let Some(data_dir) = conf_yaml.data_dir {
+            conf.data_dir = data_dir;
+        }
+        if let Some(subscriptions) = conf_yaml.subscriptions {
+            conf.subscriptions = subscriptions;
+        }
+        Ok(conf)
     }
 
     pub fn load_from_file(file_path: &str) -> Result<Self, Box<dyn std::error::Error>> {
@@ -62,46 +226,291 @@ impl Config {
         let mut conf: Config = Self::new();
         let conf_yaml: ConfigYaml = serde_yaml::from_reader(file)?;
         
-        conf.name          = conf_yaml.name;
-        conf.address       = conf_yaml.address;
-        conf.payload_file  = conf_yaml.payload_file;
-        conf.message_num   = conf_yaml.message_num;
-        conf.message_size  = Self::get_payload_len(conf.payload_file.clone());
-        conf.producer_rate = conf_yaml.producer_rate;
+        conf.name    = conf_yaml.name;
+        conf.address = conf_yaml.address;
         
-        if conf_yaml.scope == None {
-            conf.scope = Self::generate_name("scope".to_string());
-        } else {
-            conf.scope = conf_yaml.scope.unwrap_or("scope".to_string());
+        if let Some(mode) = conf_yaml.mode {
+            conf.mode = mode;
         }
-        if conf_yaml.stream == None {
-            conf.stream = Self::generate_name("stream".to_string());
-        } else {
-            conf.stream = conf_yaml.stream.unwrap_or("stream".to_string());
+        if let Some(read_message_num) = conf_yaml.read_message_num {
+            conf.read_message_num = read_message_num;
+        }
+        let read_only = conf.mode == "read_only";
+        if read_only && (conf_yaml.scope.is_none() || conf_yaml.stream.is_none()) {
+            return Err("read_only needs the scope and stream to read".into());
         }
+        match conf_yaml.producer_rate {
+            Some(producer_rate) => conf.producer_rate = producer_rate,
+            None if !read_only  => return Err("producer_rate is required unless the mode is read_only".into()),
+            None                => {},
+        }
+        // Reader groups keep their positions, a new group reads the existing stream from its head
+        if read_only {
+            conf.reader_group_suffix = format!("-{}", Utc::now().timestamp_millis());
+        }
+        conf.scope  = conf_yaml.scope.unwrap_or_else(|| Self::generate_name("scope".to_string()));
+        conf.stream = conf_yaml.stream.unwrap_or_else(|| Self::generate_name("stream".to_string()));
 
-        if conf_yaml.message_warmup != None {
-            conf.message_warmup = conf_yaml.message_warmup.unwrap_or(conf.message_warmup);
+        if let Some(payload_type) = conf_yaml.payload_type {
+            conf.payload_type = payload_type;
+        }
+        if let Some(payload_file) = conf_yaml.payload_file {
+            conf.payload_file = payload_file;
+        }
+        if let Some(payload_size) = conf_yaml.payload_size {
+            conf.payload_size = payload_size;
+        }
+        if let Some(payload_count) = conf_yaml.payload_count {
+            conf.payload_count = payload_count;
+        }
+        if let Some(payload_seed) = conf_yaml.payload_seed {
+            conf.payload_seed = payload_seed;
+        }
+        if let Some(payload_compress_ratio) = conf_yaml.payload_compress_ratio {
+            conf.payload_compress_ratio = payload_compress_ratio;
+        }
+        if let Some(payload_dir) = conf_yaml.payload_dir {
+            conf.payload_dir = payload_dir;
+        }
+        // Events are as big as the payload unless message_size sets their sizes.
+        if conf.payload_type == "file" && !read_only {
+            if conf.payload_file.is_empty() {
+                return Err("payload_file is required with payload_type file".into());
+            }
+            conf.payload_size = Self::get_payload_len(conf.payload_file.clone());
+        }
+        conf.size_distribution = match conf_yaml.message_size {
+            Some(MessageSize::Bytes(size))                => SizeDistribution::Fixed { size },
+            Some(MessageSize::Distribution(distribution)) => distribution,
+            None => SizeDistribution::Fixed { size: conf.payload_size },
+        };
+        conf.message_size = conf.size_distribution.mean()?.round() as u64;
+        SizeSampler::new(&conf.size_distribution, conf.payload_seed, 0)?;
+
+        if let Some(driver) = conf_yaml.driver {
+            conf.driver = driver;
+        }
+        if let Some(admin_address) = conf_yaml.admin_address {
+            conf.admin_address = admin_address;
+        }
+        if let Some(tenant) = conf_yaml.tenant {
+            conf.tenant = tenant;
+        }
+        if let Some(producers) = conf_yaml.producers {
+            conf.producers = producers;
+        }
+        if let Some(arrival) = conf_yaml.arrival {
+            conf.arrival = arrival;
+        }
+        if let Some(routing_key) = conf_yaml.routing_key {
+            conf.routing_key = routing_key;
+        }
+        if let Some(routing_key_count) = conf_yaml.routing_key_count {
+            conf.routing_key_count = routing_key_count;
+        }
+        if let Some(routing_key_zipf_exponent) = conf_yaml.routing_key_zipf_exponent {
+            conf.routing_key_zipf_exponent = routing_key_zipf_exponent;
+        }
+        if let Some(separate_clients) = conf_yaml.separate_clients {
+            conf.separate_clients = separate_clients;
+        }
+        if let Some(consumers) = conf_yaml.consumers {
+            conf.consumers = consumers;
+        }
+        if let Some(subscriptions) = conf_yaml.subscriptions {
+            conf.subscriptions = subscriptions;
+        }
+        if let Some(message_num) = conf_yaml.message_num {
+            conf.message_num = message_num;
+        }
+        if let Some(message_warmup) = conf_yaml.message_warmup {
+            conf.message_warmup = message_warmup;
+        }
+        if let Some(duration) = conf_yaml.duration {
+            conf.duration = duration;
+        }
+        if let Some(warmup_duration) = conf_yaml.warmup_duration {
+            conf.warmup_duration = warmup_duration;
+        }
+        if let Some(retention_type) = conf_yaml.retention_type {
+            conf.retention_type = retention_type;
+        }
+        if let Some(retention_time) = conf_yaml.retention_time {
+            conf.retention_time = retention_time;
+        }
+        if let Some(retention_size) = conf_yaml.retention_size {
+            conf.retention_size = retention_size;
+        }
+        if let Some(retention_check) = conf_yaml.retention_check {
+            conf.retention_check = retention_check;
         }
-        if conf_yaml.retention_time != None {
-            conf.retention_time = conf_yaml.retention_time.unwrap_or(conf.retention_time);
+        if let Some(retention_check_timeout) = conf_yaml.retention_check_timeout {
+            conf.retention_check_timeout = retention_check_timeout;
         }
-        if conf_yaml.scale_target_rate != None {
-            conf.scale_target_rate = conf_yaml.scale_target_rate.unwrap_or(conf.scale_target_rate);
+        if let Some(scale_type) = conf_yaml.scale_type {
+            conf.scale_type = scale_type;
         }
-        if conf_yaml.scale_factor != None {
-            conf.scale_factor = conf_yaml.scale_factor.unwrap_or(conf.scale_factor);
+        if let Some(scale_target_rate) = conf_yaml.scale_target_rate {
+            conf.scale_target_rate = scale_target_rate;
         }
-        if conf_yaml.scale_min_num_segments != None {
-            conf.scale_min_num_segments = conf_yaml.scale_min_num_segments.unwrap_or(conf.scale_min_num_segments);
+        if let Some(scale_factor) = conf_yaml.scale_factor {
+            conf.scale_factor = scale_factor;
+        }
+        if let Some(scale_min_num_segments) = conf_yaml.scale_min_num_segments {
+            conf.scale_min_num_segments = scale_min_num_segments;
+        }
+        if let Some(segment_poll_interval) = conf_yaml.segment_poll_interval {
+            conf.segment_poll_interval = segment_poll_interval;
+        }
+        if let Some(data_dir) = conf_yaml.data_dir {
+            conf.data_dir = data_dir;
+        }
+        if let Some(file_segment_size) = conf_yaml.file_segment_size {
+            conf.file_segment_size = file_segment_size;
+        }
+        if let Some(file_sync) = conf_yaml.file_sync {
+            conf.file_sync = file_sync;
+        }
+        if let Some(file_sync_batch) = conf_yaml.file_sync_batch {
+            conf.file_sync_batch = file_sync_batch;
+        }
+        if let Some(timeseries_interval) = conf_yaml.timeseries_interval {
+            conf.timeseries_interval = timeseries_interval;
+        }
+        if let Some(timeseries_csv) = conf_yaml.timeseries_csv {
+            conf.timeseries_csv = timeseries_csv;
+        }
+        if let Some(report_interval) = conf_yaml.report_interval {
+            conf.report_interval = report_interval;
+        }
+        if let Some(report_file) = conf_yaml.report_file {
+            conf.report_file = report_file;
+        }
+        if let Some(metrics_address) = conf_yaml.metrics_address {
+            conf.metrics_address = metrics_address;
+        }
+        if let Some(verify) = conf_yaml.verify {
+            conf.verify = verify;
+        }
+        if let Some(read_mode) = conf_yaml.read_mode {
+            conf.read_mode = read_mode;
+        }
+        if let Some(catchup_delay) = conf_yaml.catchup_delay {
+            conf.catchup_delay = catchup_delay;
+        }
+        if let Some(cleanup) = conf_yaml.cleanup {
+            conf.cleanup = cleanup;
+        }
+
+        if !["file", "random", "compressible", "text", "corpus"].contains(&conf.payload_type.as_str()) {
+            return Err(format!("Unknown payload_type '{}'", conf.payload_type).into());
+        }
+        if conf.payload_count == 0 {
+            return Err("payload_count must be at least 1".into());
+        }
+        if !(0.0..=1.0).contains(&conf.payload_compress_ratio) {
+            return Err("payload_compress_ratio must be between 0 and 1".into());
+        }
+        if conf.payload_type == "corpus" && conf.payload_dir.is_empty() {
+            return Err("payload_dir is required with payload_type corpus".into());
+        }
+        if !["write_only", "read_only", "both"].contains(&conf.mode.as_str()) {
+            return Err(format!("Unknown mode '{}'", conf.mode).into());
+        }
+        /*
+         * A read-only run attaches to a stream written by someone else: the
+         * memory driver has no such stream, the events carry no verification
+         * header and the stream must survive the run.
+         */
+        if read_only && conf.driver == "memory" {
+            return Err("read_only cannot attach to a stream of the memory driver".into());
+        }
+        if read_only && (conf.verify || conf.cleanup) {
+            return Err("verify and cleanup are not available in read_only mode".into());
+        }
+        if conf.mode != "both" && conf.read_mode == "catchup" {
+            return Err("read_mode catchup needs mode both".into());
+        }
+        if conf.message_num == 0 && conf.duration == 0 && !read_only {
+            return Err("Either message_num or duration must be set".into());
+        }
+        if !read_only && (conf.producers == 0 || conf.producers > conf.producer_rate) {
+            return Err(format!("producers must be between 1 and producer_rate ({})", conf.producer_rate).into());
+        }
+        if !["uniform", "poisson"].contains(&conf.arrival.as_str()) {
+            return Err(format!("Unknown arrival distribution '{}'", conf.arrival).into());
+        }
+        KeySampler::new(&conf.routing_key, conf.routing_key_count, conf.routing_key_zipf_exponent, 0)?;
+        if !["tail", "catchup"].contains(&conf.read_mode.as_str()) {
+            return Err(format!("Unknown read_mode '{}'", conf.read_mode).into());
+        }
+        if !["FixedNumSegments", "ByRateInKbytesPerSec", "ByRateInEventsPerSec"].contains(&conf.scale_type.as_str()) {
+            return Err(format!("Unknown scale_type '{}'", conf.scale_type).into());
+        }
+        if conf.scale_min_num_segments < 1 {
+            return Err("scale_min_num_segments must be at least 1".into());
+        }
+        if conf.scale_type != "FixedNumSegments" && conf.scale_target_rate < 1 {
+            return Err(format!("scale_target_rate must be at least 1 with {}", conf.scale_type).into());
+        }
+        if !["none", "time", "size"].contains(&conf.retention_type.as_str()) {
+            return Err(format!("Unknown retention_type '{}'", conf.retention_type).into());
+        }
+        if conf.retention_time <= 0 || conf.retention_size <= 0 {
+            return Err("retention_time and retention_size must be positive".into());
+        }
+        if conf.timeseries_interval == 0 {
+            return Err("timeseries_interval must be at least 1 ms".into());
+        }
+        if conf.verify && matches!(conf.size_distribution, SizeDistribution::Fixed { size } if size < VERIFY_HEADER_LEN as u64) {
+            return Err(format!("verify needs payloads of at least {} bytes", VERIFY_HEADER_LEN).into());
+        }
+        if conf.consumers == 0 {
+            return Err("consumers must be at least 1".into());
+        }
+        if conf.subscriptions == 0 {
+            return Err("subscriptions must be at least 1".into());
         }
         Ok(conf)
     }
     
+    /*
+     * Size sampler of one sending thread, every stream of sizes gets its own
+     * seed so runs are reproducible. Verified events keep room for the header.
+     */
+    pub fn size_sampler(&self, stream: u64) -> SizeSampler {
+        let min = if self.verify { VERIFY_HEADER_LEN as u64 } else { 0 };
+        SizeSampler::new(&self.size_distribution, self.payload_seed.wrapping_add(stream), min)
+            .expect("message_size was validated")
+    }
+
+    // Routing keys of one sending thread, seeded apart from its sizes.
+    pub fn key_sampler(&self, stream: u64) -> KeySampler {
+        let seed = self.payload_seed.wrapping_add(stream).rotate_left(32);
+        KeySampler::new(&self.routing_key, self.routing_key_count, self.routing_key_zipf_exponent, seed)
+            .expect("routing_key was validated")
+    }
+
+    // Reader group of the given subscription, a single group keeps the historical name.
+    pub fn reader_group_name(&self, subscription: usize) -> String {
+        let name = if self.subscriptions == 1 {
+            "rg".to_string()
+        } else {
+            format!("rg{}", subscription + 1)
+        };
+        name + &self.reader_group_suffix
+    }
+
+    // Every reader group name a run with up to `subscriptions` subscriptions uses.
+    pub fn reader_group_names(&self) -> Vec<String> {
+        let mut names = vec!["rg".to_string()];
+        names.extend((1..=self.subscriptions).map(|subscription| format!("rg{}", subscription)));
+        names
+    }
+
     fn generate_name(init: String) -> String {
         let now = Utc::now();
-        let name = init.to_owned() + &now.timestamp().to_string();
-        name
+        init.to_owned() + &now.timestamp().to_string()
     }
 
     fn get_payload_len(file_path: String) -> u64 {
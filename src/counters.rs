@@ -0,0 +1,92 @@
+use crate::config::Config;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+
+/*
+ * Progress shared between the sender and receiver threads. Read counters start
+ * at minus the warmup messages, so they turn positive with the first benchmark
+ * event of each reader group.
+ */
+pub struct Counters {
+    pub sequence: AtomicU64,
+    pub written:  AtomicI64,
+    pub read:     Vec<AtomicI64>,
+    // Events acknowledged to every writer, warmup included.
+    pub acked:    Vec<AtomicU64>,
+    // Payload bytes of the benchmark events written and read by all reader groups.
+    pub written_bytes: AtomicU64,
+    pub read_bytes:    AtomicU64,
+}
+
+impl Counters {
+    pub fn new(conf: &Config) -> Counters {
+        Counters {
+            sequence: AtomicU64::new(0),
+            written:  AtomicI64::new(0),
+            read:     (0..conf.subscriptions).map(|_| AtomicI64::new(-(conf.message_warmup as i64))).collect(),
+            acked:    (0..conf.producers).map(|_| AtomicU64::new(0)).collect(),
+            written_bytes: AtomicU64::new(0),
+            read_bytes:    AtomicU64::new(0),
+        }
+    }
+
+    // Number of warmup messages actually sent, the readers skip as many events.
+    pub fn set_warmup(&self, messages: u32) {
+        for read in &self.read {
+            read.store(-(messages as i64), Ordering::SeqCst);
+        }
+    }
+
+    // Sequence number stamped in the header of the next event, unique across producers.
+    pub fn next_sequence(&self) -> u64 {
+        self.sequence.fetch_add(1, Ordering::SeqCst)
+    }
+
+    pub fn add_written(&self, bytes: u64) {
+        self.written.fetch_add(1, Ordering::SeqCst);
+        self.written_bytes.fetch_add(bytes, Ordering::SeqCst);
+    }
+
+    pub fn add_read_bytes(&self, bytes: u64) {
+        self.read_bytes.fetch_add(bytes, Ordering::SeqCst);
+    }
+
+    pub fn add_acked(&self, writer: usize) {
+        self.acked[writer].fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn acked(&self) -> Vec<u64> {
+        self.acked.iter().map(|acked| acked.load(Ordering::SeqCst)).collect()
+    }
+
+    // Returns the number of the read event inside its reader group.
+    pub fn add_read(&self, subscription: usize) -> i64 {
+        self.read[subscription].fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    pub fn total_written(&self) -> i64 {
+        self.written.load(Ordering::SeqCst)
+    }
+
+    pub fn total_written_bytes(&self) -> u64 {
+        self.written_bytes.load(Ordering::SeqCst)
+    }
+
+    pub fn total_read_bytes(&self) -> u64 {
+        self.read_bytes.load(Ordering::SeqCst)
+    }
+
+    // Benchmark events read by all reader groups together.
+    pub fn total_read(&self) -> i64 {
+        self.read.iter().map(|read| read.load(Ordering::SeqCst).max(0)).sum()
+    }
+
+    pub fn total_lag(&self) -> i64 {
+        (0..self.read.len()).map(|subscription| self.lag(subscription)).sum()
+    }
+
+    // Benchmark events acknowledged to the writers but not read yet by the reader group.
+    pub fn lag(&self, subscription: usize) -> i64 {
+        let read = self.read[subscription].load(Ordering::SeqCst).max(0);
+        (self.written.load(Ordering::SeqCst) - read).max(0)
+    }
+}
@@ -0,0 +1,330 @@
+use std::fs;
+use std::fs::File;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::Condvar;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::collections::HashMap;
+use crate::config::Config;
+use super::{Driver, Producer, Consumer, DriverResult};
+
+// Every record is its payload length as a little-endian u32 followed by the payload.
+const HEADER_LEN: u64 = 4;
+
+/*
+ * Storage baseline: the topic is a directory of append-only segment files.
+ * A new segment is started once the current one reaches file_segment_size.
+ * Only bytes of completely written records are exposed to readers, which
+ * tail the log and wait on the condition variable for new appends.
+ */
+struct LogState {
+    segments:       Vec<u64>,
+    writer:         Option<File>,
+    unsynced:       u32,
+    open_producers: usize,
+}
+
+struct Cursor {
+    segment: usize,
+    offset:  u64,
+    file:    Option<File>,
+}
+
+struct Log {
+    dir:      PathBuf,
+    conf:     Config,
+    state:    Mutex<LogState>,
+    appended: Condvar,
+    cursors:  Mutex<HashMap<String, Arc<Mutex<Cursor>>>>,
+}
+
+pub struct FileDriver {
+    log:           Arc<Log>,
+    // Whether create_topic created the scope directory.
+    created_scope: AtomicBool,
+}
+
+pub struct FileProducer {
+    log:    Arc<Log>,
+    closed: bool,
+}
+
+pub struct FileConsumer {
+    log:    Arc<Log>,
+    cursor: Arc<Mutex<Cursor>>,
+}
+
+impl FileDriver {
+    pub fn new(conf: Config) -> DriverResult<Self> {
+        if !["none", "event", "batch"].contains(&conf.file_sync.as_str()) {
+            return Err(format!("Unknown file_sync mode '{}'", conf.file_sync).into());
+        }
+        let dir      = PathBuf::from(&conf.data_dir).join(&conf.scope).join(&conf.stream);
+        let segments = Self::existing_segments(&dir);
+        Ok(FileDriver {
+            log: Arc::new(Log {
+                dir,
+                conf,
+                state: Mutex::new(LogState {
+                    segments,
+                    writer:         None,
+                    unsynced:       0,
+                    open_producers: 0,
+                }),
+                appended: Condvar::new(),
+                cursors:  Mutex::new(HashMap::new()),
+            }),
+            created_scope: AtomicBool::new(false),
+        })
+    }
+
+    // Lengths of the segments already on disk, so an existing stream can be appended to or read.
+    fn existing_segments(dir: &Path) -> Vec<u64> {
+        let mut segments = Vec::new();
+        while let Ok(metadata) = fs::metadata(Log::segment_path(dir, segments.len())) {
+            segments.push(metadata.len());
+        }
+        segments
+    }
+}
+
+impl Log {
+    fn segment_path(dir: &Path, segment: usize) -> PathBuf {
+        dir.join(format!("segment-{:06}.log", segment))
+    }
+
+    fn lock_state(&self) -> std::sync::MutexGuard<'_, LogState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn append(&self, payload: &[u8]) -> DriverResult<()> {
+        let mut record = Vec::with_capacity(HEADER_LEN as usize + payload.len());
+        record.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        record.extend_from_slice(payload);
+
+        let mut state = self.lock_state();
+        let roll = match state.segments.last() {
+            Some(&len) => len > 0 && len + record.len() as u64 > self.conf.file_segment_size,
+            None       => true,
+        };
+        if roll || state.writer.is_none() {
+            if roll {
+                if let Some(writer) = state.writer.as_mut() {
+                    writer.sync_all()?;
+                }
+                state.segments.push(0);
+            }
+            let path = Self::segment_path(&self.dir, state.segments.len() - 1);
+            state.writer = Some(fs::OpenOptions::new().create(true).append(true).open(path)?);
+        }
+
+        let writer = state.writer.as_mut().unwrap();
+        writer.write_all(&record)?;
+        match self.conf.file_sync.as_str() {
+            "event" => writer.sync_data()?,
+            "batch" => {
+                state.unsynced += 1;
+                if state.unsynced >= self.conf.file_sync_batch {
+                    state.writer.as_mut().unwrap().sync_data()?;
+                    state.unsynced = 0;
+                }
+            },
+            _ => {},
+        }
+        *state.segments.last_mut().unwrap() += record.len() as u64;
+        self.appended.notify_all();
+        Ok(())
+    }
+}
+
+impl Driver for FileDriver {
+    fn name(&self) -> &str {
+        "file"
+    }
+
+    fn create_topic(&self) -> DriverResult<()> {
+        let scope_dir = PathBuf::from(&self.log.conf.data_dir).join(&self.log.conf.scope);
+        self.created_scope.store(!scope_dir.exists(), Ordering::SeqCst);
+        fs::create_dir_all(&self.log.dir)?;
+        Ok(())
+    }
+
+    fn delete_topic(&self) -> DriverResult<()> {
+        let mut state = self.log.lock_state();
+        state.writer = None;
+        fs::remove_dir_all(&self.log.dir)?;
+        if self.created_scope.load(Ordering::SeqCst) {
+            fs::remove_dir_all(PathBuf::from(&self.log.conf.data_dir).join(&self.log.conf.scope))?;
+        }
+        Ok(())
+    }
+
+    // Scopes are the directories under data_dir.
+    fn list_scopes(&self) -> DriverResult<Vec<String>> {
+        let mut scopes = Vec::new();
+        for entry in fs::read_dir(&self.log.conf.data_dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                scopes.push(entry.file_name().to_string_lossy().into_owned());
+            }
+        }
+        Ok(scopes)
+    }
+
+    fn delete_scope(&self, scope: &str) -> DriverResult<usize> {
+        let dir     = PathBuf::from(&self.log.conf.data_dir).join(scope);
+        let streams = fs::read_dir(&dir)?.count();
+        fs::remove_dir_all(dir)?;
+        Ok(streams)
+    }
+
+    fn create_producer(&self) -> DriverResult<Box<dyn Producer>> {
+        let mut state = self.log.lock_state();
+        state.open_producers += 1;
+        Ok(Box::new(FileProducer { log: Arc::clone(&self.log), closed: false }))
+    }
+
+    fn create_consumer(&self, group: &str, _reader: &str) -> DriverResult<Box<dyn Consumer>> {
+        let mut cursors = self.log.cursors.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let cursor = cursors
+            .entry(group.to_string())
+            .or_insert_with(|| Arc::new(Mutex::new(Cursor { segment: 0, offset: 0, file: None })));
+        Ok(Box::new(FileConsumer { log: Arc::clone(&self.log), cursor: Arc::clone(cursor) }))
+    }
+}
+
+impl Producer for FileProducer {
+    // A single log keeps the order of all events, so routing keys make no difference.
+    fn send(&mut self, _routing_key: Option<String>, payload: Vec<u8>) -> DriverResult<()> {
+        self.log.append(&payload)
+    }
+
+    fn close(&mut self) -> DriverResult<()> {
+        if !self.closed {
+            self.closed = true;
+            let mut state = self.log.lock_state();
+            if let Some(writer) = state.writer.as_mut() {
+                writer.sync_all()?;
+            }
+            state.open_producers -= 1;
+            self.log.appended.notify_all();
+        }
+        Ok(())
+    }
+}
+
+impl Drop for FileProducer {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+impl Consumer for FileConsumer {
+    // Tails the log; it is drained once no producer is open and all records were read.
+    fn receive(&mut self) -> DriverResult<Option<Vec<u8>>> {
+        let mut cursor = self.cursor.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut state  = self.log.lock_state();
+        loop {
+            let committed = state.segments.get(cursor.segment).copied().unwrap_or(0);
+            if cursor.offset < committed {
+                break;
+            }
+            if cursor.segment + 1 < state.segments.len() {
+                cursor.segment += 1;
+                cursor.offset   = 0;
+                cursor.file     = None;
+                continue;
+            }
+            if state.open_producers == 0 {
+                return Ok(None);
+            }
+            state = self.log.appended
+                .wait_timeout(state, Duration::from_millis(100))
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .0;
+        }
+        drop(state);
+
+        if cursor.file.is_none() {
+            let mut file = File::open(Log::segment_path(&self.log.dir, cursor.segment))?;
+            file.seek(SeekFrom::Start(cursor.offset))?;
+            cursor.file = Some(file);
+        }
+        let file = cursor.file.as_mut().unwrap();
+        let mut header = [0u8; HEADER_LEN as usize];
+        file.read_exact(&mut header)?;
+        let mut payload = vec![0u8; u32::from_le_bytes(header) as usize];
+        file.read_exact(&mut payload)?;
+        cursor.offset += HEADER_LEN + payload.len() as u64;
+        Ok(Some(payload))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn test_conf(name: &str, segment_size: u64) -> Config {
+        let mut conf = Config::new();
+        conf.data_dir          = std::env::temp_dir().join(format!("file-driver-{}-{}", name, std::process::id())).to_string_lossy().into_owned();
+        conf.file_segment_size = segment_size;
+        let _ = fs::remove_dir_all(&conf.data_dir);
+        conf
+    }
+
+    fn drain(consumer: &mut Box<dyn Consumer>) -> Vec<Vec<u8>> {
+        let mut events = Vec::new();
+        while let Some(event) = consumer.receive().unwrap() {
+            events.push(event);
+        }
+        events
+    }
+
+    #[test]
+    fn segments_roll_over_at_the_configured_size() {
+        // Records of 14 bytes, only one fits in a segment of 20
+        let conf   = test_conf("roll", 20);
+        let driver = FileDriver::new(conf.clone()).unwrap();
+        driver.create_topic().unwrap();
+        let mut producer = driver.create_producer().unwrap();
+        for i in 0..3u8 {
+            producer.send(None, vec![i; 10]).unwrap();
+        }
+        producer.close().unwrap();
+        for segment in 0..3 {
+            assert_eq!(fs::metadata(Log::segment_path(&driver.log.dir, segment)).unwrap().len(), 14);
+        }
+        assert!(fs::metadata(Log::segment_path(&driver.log.dir, 3)).is_err());
+
+        let mut consumer = driver.create_consumer("rg1", "r1").unwrap();
+        assert_eq!(drain(&mut consumer), vec![vec![0; 10], vec![1; 10], vec![2; 10]]);
+        fs::remove_dir_all(&conf.data_dir).unwrap();
+    }
+
+    #[test]
+    fn readers_tail_until_every_producer_closed() {
+        let conf   = test_conf("tail", 1024);
+        let driver = FileDriver::new(conf.clone()).unwrap();
+        driver.create_topic().unwrap();
+        let mut producer = driver.create_producer().unwrap();
+        let mut consumer = driver.create_consumer("rg1", "r1").unwrap();
+        producer.send(None, vec![1]).unwrap();
+        assert_eq!(consumer.receive().unwrap(), Some(vec![1]));
+
+        let reader = thread::spawn(move || drain(&mut consumer));
+        thread::sleep(Duration::from_millis(200));
+        producer.send(None, vec![2]).unwrap();
+        producer.close().unwrap();
+        assert_eq!(reader.join().unwrap(), vec![vec![2]]);
+
+        // A new driver drains the stream already on disk
+        let existing = FileDriver::new(conf.clone()).unwrap();
+        let mut consumer = existing.create_consumer("rg1", "r1").unwrap();
+        assert_eq!(drain(&mut consumer), vec![vec![1], vec![2]]);
+        fs::remove_dir_all(&conf.data_dir).unwrap();
+    }
+}
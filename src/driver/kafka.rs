@@ -0,0 +1,269 @@
+use std::thread;
+use std::sync::Arc;
+use std::sync::mpsc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use std::time::Instant;
+use crate::config::Config;
+use rdkafka::Offset;
+use rdkafka::ClientConfig;
+use rdkafka::message::Message;
+use rdkafka::util::Timeout;
+use rdkafka::types::RDKafkaErrorCode;
+use rdkafka::mocking::MockCluster;
+use rdkafka::consumer::{BaseConsumer, Consumer as KafkaConsumerApi};
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use rdkafka::admin::{AdminClient, AdminOptions, NewTopic, TopicReplication};
+use rdkafka::client::DefaultClientContext;
+use tokio::runtime::{Handle, Runtime};
+use super::{Driver, Producer, Consumer, DriverResult, FIRST_EVENT_TIMEOUT, IDLE_TIMEOUT};
+
+// Address that makes the driver start an in-process librdkafka mock broker.
+const MOCK_ADDRESS: &str = "mock";
+// Time the consumer waits for the high watermark of a partition.
+const WATERMARK_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct KafkaDriver {
+    conf:              Config,
+    topic:             String,
+    bootstrap_servers: String,
+    runtime:           Runtime,
+    // Keeps the mock broker thread alive for as long as the driver exists.
+    mock_guard:        Option<mpsc::Sender<()>>,
+    open_producers:    Arc<AtomicUsize>,
+}
+
+pub struct KafkaProducer {
+    handle:         Handle,
+    topic:          String,
+    producer:       FutureProducer,
+    open_producers: Arc<AtomicUsize>,
+    closed:         bool,
+}
+
+pub struct KafkaConsumer {
+    consumer:       BaseConsumer,
+    topic:          String,
+    partition:      Option<i32>,
+    last_event:     Option<Instant>,
+    created:        Instant,
+    open_producers: Arc<AtomicUsize>,
+}
+
+impl KafkaDriver {
+    pub fn new(conf: Config) -> DriverResult<Self> {
+        let topic = format!("{}.{}", conf.scope, conf.stream);
+        let (bootstrap_servers, mock_guard) = if conf.address == MOCK_ADDRESS {
+            let (servers, guard) = Self::start_mock_cluster(topic.clone(), conf.scale_min_num_segments)?;
+            (servers, Some(guard))
+        } else {
+            (conf.address.clone(), None)
+        };
+        Ok(KafkaDriver {
+            topic,
+            conf,
+            bootstrap_servers,
+            runtime: Runtime::new()?,
+            mock_guard,
+            open_producers: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    /*
+     * The mock cluster is not Send, so it lives on its own thread until the
+     * guard sender is dropped together with the driver. The mock broker does
+     * not implement the admin API, so the topic is created here as well.
+     */
+    fn start_mock_cluster(topic: String, partitions: i32) -> DriverResult<(String, mpsc::Sender<()>)> {
+        let (servers_tx, servers_rx) = mpsc::channel();
+        let (guard_tx, guard_rx)     = mpsc::channel::<()>();
+        thread::spawn(move || {
+            let cluster = MockCluster::new(1)
+                .and_then(|cluster| cluster.create_topic(&topic, partitions, 1).map(|_| cluster));
+            match cluster {
+                Ok(cluster) => {
+                    let _ = servers_tx.send(Ok(cluster.bootstrap_servers()));
+                    let _ = guard_rx.recv();
+                },
+                Err(e) => {
+                    let _ = servers_tx.send(Err(e.to_string()));
+                }
+            }
+        });
+        let servers = servers_rx.recv()??;
+        Ok((servers, guard_tx))
+    }
+
+    fn client_config(&self) -> ClientConfig {
+        let mut client_config = ClientConfig::new();
+        client_config.set("bootstrap.servers", &self.bootstrap_servers);
+        client_config
+    }
+}
+
+impl Driver for KafkaDriver {
+    fn name(&self) -> &str {
+        "kafka"
+    }
+
+    fn create_topic(&self) -> DriverResult<()> {
+        if self.mock_guard.is_some() {
+            return Ok(());
+        }
+        let admin: AdminClient<DefaultClientContext> = self.client_config().create()?;
+        let topic   = NewTopic::new(&self.topic, self.conf.scale_min_num_segments, TopicReplication::Fixed(1));
+        let results = self.runtime.block_on(admin.create_topics(&[topic], &AdminOptions::new()))?;
+        for result in results {
+            match result {
+                Ok(_) | Err((_, RDKafkaErrorCode::TopicAlreadyExists)) => {},
+                Err((name, code)) => return Err(format!("Failed to create topic {}: {}", name, code).into()),
+            }
+        }
+        Ok(())
+    }
+
+    fn delete_topic(&self) -> DriverResult<()> {
+        if self.mock_guard.is_some() {
+            return Ok(());
+        }
+        let admin: AdminClient<DefaultClientContext> = self.client_config().create()?;
+        let results = self.runtime.block_on(admin.delete_topics(&[&self.topic], &AdminOptions::new()))?;
+        for result in results {
+            if let Err((name, code)) = result {
+                return Err(format!("Failed to delete topic {}: {}", name, code).into());
+            }
+        }
+        Ok(())
+    }
+
+    // Consumer groups keep their committed offsets, groups that are already gone are fine.
+    fn delete_groups(&self) -> DriverResult<()> {
+        if self.mock_guard.is_some() {
+            return Ok(());
+        }
+        let groups: Vec<String> = (0..self.conf.subscriptions as usize).map(|subscription| self.conf.reader_group_name(subscription)).collect();
+        let groups: Vec<&str>   = groups.iter().map(String::as_str).collect();
+        let admin: AdminClient<DefaultClientContext> = self.client_config().create()?;
+        let results = self.runtime.block_on(admin.delete_groups(&groups, &AdminOptions::new()))?;
+        for result in results {
+            match result {
+                Ok(_) | Err((_, RDKafkaErrorCode::GroupIdNotFound)) => {},
+                Err((name, code)) => return Err(format!("Failed to delete consumer group {}: {}", name, code).into()),
+            }
+        }
+        Ok(())
+    }
+
+    fn create_producer(&self) -> DriverResult<Box<dyn Producer>> {
+        let producer: FutureProducer = self.client_config().create()?;
+        self.open_producers.fetch_add(1, Ordering::SeqCst);
+        Ok(Box::new(KafkaProducer {
+            handle:         self.runtime.handle().clone(),
+            topic:          self.topic.clone(),
+            producer,
+            open_producers: Arc::clone(&self.open_producers),
+            closed:         false,
+        }))
+    }
+
+    fn create_consumer(&self, group: &str, reader: &str) -> DriverResult<Box<dyn Consumer>> {
+        let consumer: BaseConsumer = self.client_config()
+            .set("group.id", group)
+            .set("client.id", reader)
+            .set("auto.offset.reset", "earliest")
+            .create()?;
+        consumer.subscribe(&[&self.topic])?;
+        Ok(Box::new(KafkaConsumer {
+            consumer,
+            topic:          self.topic.clone(),
+            partition:      None,
+            last_event:     None,
+            created:        Instant::now(),
+            open_producers: Arc::clone(&self.open_producers),
+        }))
+    }
+}
+
+impl Producer for KafkaProducer {
+    // Keyed records go to the partition of their key, the others are spread by the partitioner.
+    fn send(&mut self, routing_key: Option<String>, payload: Vec<u8>) -> DriverResult<()> {
+        let mut record = FutureRecord::<String, _>::to(&self.topic).payload(&payload);
+        if let Some(key) = routing_key.as_ref() {
+            record = record.key(key);
+        }
+        self.handle
+            .block_on(self.producer.send(record, Timeout::Never))
+            .map_err(|(e, _)| e)?;
+        Ok(())
+    }
+
+    fn close(&mut self) -> DriverResult<()> {
+        if !self.closed {
+            rdkafka::producer::Producer::flush(&self.producer, Timeout::Never)?;
+            self.closed = true;
+            self.open_producers.fetch_sub(1, Ordering::SeqCst);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for KafkaProducer {
+    fn drop(&mut self) {
+        if !self.closed {
+            self.open_producers.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
+
+impl KafkaConsumer {
+    // Whether the consumer read up to the high watermark of every partition assigned to it.
+    fn caught_up(&self) -> DriverResult<bool> {
+        for element in self.consumer.position()?.elements() {
+            let (low, high) = self.consumer.fetch_watermarks(&self.topic, element.partition(), WATERMARK_TIMEOUT)?;
+            let position = match element.offset() {
+                Offset::Offset(offset) => offset,
+                // Nothing read from the partition yet
+                _                      => low,
+            };
+            if position < high {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+impl Consumer for KafkaConsumer {
+    /*
+     * Kafka has no end of stream, so the topic is considered drained once every
+     * producer is closed, the consumer reached the end of its partitions and no
+     * event arrived for a while, like the Pravega reader running out of segments.
+     * While producers are open a write stall only makes the consumer wait.
+     */
+    fn receive(&mut self) -> DriverResult<Option<Vec<u8>>> {
+        loop {
+            if let Some(message) = self.consumer.poll(Duration::from_millis(100)) {
+                let message = message?;
+                self.last_event = Some(Instant::now());
+                self.partition  = Some(message.partition());
+                return Ok(Some(message.payload().unwrap_or_default().to_vec()));
+            }
+            let idle = match self.last_event {
+                Some(last) => last.elapsed() > IDLE_TIMEOUT,
+                None       => self.created.elapsed() > FIRST_EVENT_TIMEOUT,
+            };
+            if idle && self.open_producers.load(Ordering::SeqCst) == 0 && self.caught_up()? {
+                return Ok(None);
+            }
+        }
+    }
+
+    fn segment(&self) -> Option<String> {
+        self.partition.map(|partition| format!("partition-{}", partition))
+    }
+
+    fn close(&mut self) -> DriverResult<()> {
+        self.consumer.unsubscribe();
+        Ok(())
+    }
+}
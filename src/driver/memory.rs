@@ -0,0 +1,206 @@
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::Condvar;
+use std::time::Duration;
+use std::hash::{Hash, Hasher};
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use super::{Driver, Producer, Consumer, DriverResult};
+
+/*
+ * In-process reference backend. The topic is an append-only log shared by all
+ * producers; every reader group keeps its own offset into it, so readers of the
+ * same group split the events and different groups each see all of them.
+ * Every event is tagged with one of `segments` emulated segments, picked by
+ * the hash of its routing key or round-robin for events without one.
+ */
+#[derive(Default)]
+struct Log {
+    segments:       u32,
+    unkeyed:        u32,
+    events:         Vec<(u32, Arc<Vec<u8>>)>,
+    groups:         HashMap<String, usize>,
+    open_producers: usize,
+    had_producers:  bool,
+}
+
+type SharedLog = Arc<(Mutex<Log>, Condvar)>;
+
+pub struct MemoryDriver {
+    log: SharedLog,
+}
+
+pub struct MemoryProducer {
+    log:    SharedLog,
+    closed: bool,
+}
+
+pub struct MemoryConsumer {
+    log:     SharedLog,
+    group:   String,
+    segment: Option<u32>,
+}
+
+impl MemoryDriver {
+    pub fn new(segments: i32) -> Self {
+        let log = Log { segments: segments.max(1) as u32, ..Log::default() };
+        MemoryDriver { log: Arc::new((Mutex::new(log), Condvar::new())) }
+    }
+}
+
+fn lock_log(log: &SharedLog) -> std::sync::MutexGuard<'_, Log> {
+    log.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+impl Driver for MemoryDriver {
+    fn name(&self) -> &str {
+        "memory"
+    }
+
+    fn create_topic(&self) -> DriverResult<()> {
+        Ok(())
+    }
+
+    // Nothing outlives the process.
+    fn delete_topic(&self) -> DriverResult<()> {
+        Ok(())
+    }
+
+    fn create_producer(&self) -> DriverResult<Box<dyn Producer>> {
+        let mut log = lock_log(&self.log);
+        log.open_producers += 1;
+        log.had_producers   = true;
+        Ok(Box::new(MemoryProducer { log: Arc::clone(&self.log), closed: false }))
+    }
+
+    fn create_consumer(&self, group: &str, _reader: &str) -> DriverResult<Box<dyn Consumer>> {
+        lock_log(&self.log).groups.entry(group.to_string()).or_insert(0);
+        Ok(Box::new(MemoryConsumer { log: Arc::clone(&self.log), group: group.to_string(), segment: None }))
+    }
+
+    fn segment_count(&self) -> DriverResult<Option<usize>> {
+        Ok(Some(lock_log(&self.log).segments as usize))
+    }
+}
+
+impl Producer for MemoryProducer {
+    fn send(&mut self, routing_key: Option<String>, payload: Vec<u8>) -> DriverResult<()> {
+        let mut log = lock_log(&self.log);
+        let segment = match routing_key {
+            Some(key) => {
+                let mut hasher = DefaultHasher::new();
+                key.hash(&mut hasher);
+                (hasher.finish() % log.segments as u64) as u32
+            },
+            None => {
+                log.unkeyed += 1;
+                log.unkeyed % log.segments
+            },
+        };
+        log.events.push((segment, Arc::new(payload)));
+        drop(log);
+        self.log.1.notify_all();
+        Ok(())
+    }
+
+    fn close(&mut self) -> DriverResult<()> {
+        if !self.closed {
+            self.closed = true;
+            lock_log(&self.log).open_producers -= 1;
+            self.log.1.notify_all();
+        }
+        Ok(())
+    }
+}
+
+impl Drop for MemoryProducer {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+impl Consumer for MemoryConsumer {
+    // Waits for the next event; the log is drained once every producer closed.
+    fn receive(&mut self) -> DriverResult<Option<Vec<u8>>> {
+        let mut log = lock_log(&self.log);
+        loop {
+            let offset = log.groups[&self.group];
+            if offset < log.events.len() {
+                let (segment, event) = (log.events[offset].0, Arc::clone(&log.events[offset].1));
+                log.groups.insert(self.group.clone(), offset + 1);
+                self.segment = Some(segment);
+                return Ok(Some(event.as_ref().clone()));
+            }
+            if log.had_producers && log.open_producers == 0 {
+                return Ok(None);
+            }
+            log = self.log.1
+                .wait_timeout(log, Duration::from_millis(100))
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .0;
+        }
+    }
+
+    fn segment(&self) -> Option<String> {
+        self.segment.map(|segment| format!("segment-{}", segment))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drain(consumer: &mut Box<dyn Consumer>) -> Vec<Vec<u8>> {
+        let mut events = Vec::new();
+        while let Some(event) = consumer.receive().unwrap() {
+            events.push(event);
+        }
+        events
+    }
+
+    #[test]
+    fn every_group_reads_all_events() {
+        let driver = MemoryDriver::new(2);
+        let mut producer = driver.create_producer().unwrap();
+        let mut first    = driver.create_consumer("rg1", "r1").unwrap();
+        let mut second   = driver.create_consumer("rg2", "r1").unwrap();
+        for i in 0..3u8 {
+            producer.send(None, vec![i]).unwrap();
+        }
+        producer.close().unwrap();
+        assert_eq!(drain(&mut first), vec![vec![0], vec![1], vec![2]]);
+        assert_eq!(drain(&mut second), vec![vec![0], vec![1], vec![2]]);
+    }
+
+    #[test]
+    fn readers_of_a_group_split_the_events() {
+        let driver = MemoryDriver::new(1);
+        let mut producer = driver.create_producer().unwrap();
+        let mut first    = driver.create_consumer("rg1", "r1").unwrap();
+        let mut second   = driver.create_consumer("rg1", "r2").unwrap();
+        for i in 0..4u8 {
+            producer.send(None, vec![i]).unwrap();
+        }
+        producer.close().unwrap();
+        assert_eq!(first.receive().unwrap(), Some(vec![0]));
+        assert_eq!(second.receive().unwrap(), Some(vec![1]));
+        assert_eq!(drain(&mut first).len() + drain(&mut second).len(), 2);
+    }
+
+    #[test]
+    fn keyed_events_stay_in_one_segment() {
+        let driver = MemoryDriver::new(4);
+        let mut producer = driver.create_producer().unwrap();
+        let mut consumer = driver.create_consumer("rg1", "r1").unwrap();
+        for _ in 0..8 {
+            producer.send(Some("key-0".to_string()), vec![0]).unwrap();
+        }
+        producer.close().unwrap();
+        consumer.receive().unwrap();
+        let segment = consumer.segment();
+        while consumer.receive().unwrap().is_some() {
+            assert_eq!(consumer.segment(), segment);
+        }
+        assert_eq!(driver.segment_count().unwrap(), Some(4));
+    }
+}
@@ -0,0 +1,95 @@
+mod file;
+mod memory;
+mod pravega;
+#[cfg(feature = "kafka")]
+mod kafka;
+#[cfg(feature = "pulsar")]
+mod pulsar;
+
+use std::sync::Arc;
+use crate::config::Config;
+use file::FileDriver;
+use memory::MemoryDriver;
+use pravega::PravegaDriver;
+
+// Time a consumer waits for the first event before considering the topic empty.
+#[cfg(any(feature = "kafka", feature = "pulsar"))]
+const FIRST_EVENT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+// Time without events after which a consumer considers the topic drained, once no producer is open.
+#[cfg(any(feature = "kafka", feature = "pulsar"))]
+const IDLE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(1);
+
+pub type DriverResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/*
+ * A messaging system under test. The driver owns whatever connection state
+ * the system needs and hands out producers and consumers bound to the
+ * benchmark topic (scope/stream in Pravega terms).
+ */
+pub trait Driver: Send + Sync {
+    fn name(&self) -> &str;
+    fn create_topic(&self) -> DriverResult<()>;
+    fn create_producer(&self) -> DriverResult<Box<dyn Producer>>;
+    fn create_consumer(&self, group: &str, reader: &str) -> DriverResult<Box<dyn Consumer>>;
+    // Removes the topic after the run, and its scope if create_topic created it.
+    fn delete_topic(&self) -> DriverResult<()> {
+        Err(format!("Driver '{}' cannot delete topics", self.name()).into())
+    }
+    // Removes the reader groups created by create_consumer, for systems that keep their positions.
+    fn delete_groups(&self) -> DriverResult<()> {
+        Ok(())
+    }
+    // Scopes (namespaces) of the system, used to clean up old benchmark runs.
+    fn list_scopes(&self) -> DriverResult<Vec<String>> {
+        Err(format!("Driver '{}' cannot list scopes", self.name()).into())
+    }
+    // Deletes a scope with all its streams, returns the number of streams deleted.
+    fn delete_scope(&self, _scope: &str) -> DriverResult<usize> {
+        Err(format!("Driver '{}' cannot delete scopes", self.name()).into())
+    }
+    // Current number of segments (or partitions) of the topic, None if the system cannot tell.
+    fn segment_count(&self) -> DriverResult<Option<usize>> {
+        Ok(None)
+    }
+    // Whether data was truncated from the head of the topic, None if the system cannot tell.
+    fn head_truncated(&self) -> DriverResult<Option<bool>> {
+        Ok(None)
+    }
+}
+
+pub trait Producer: Send {
+    // Blocks until the event is acknowledged by the system, events with the same key keep their order.
+    fn send(&mut self, routing_key: Option<String>, payload: Vec<u8>) -> DriverResult<()>;
+    fn close(&mut self) -> DriverResult<()> {
+        Ok(())
+    }
+}
+
+pub trait Consumer: Send {
+    // Returns None once there is no more data to read.
+    fn receive(&mut self) -> DriverResult<Option<Vec<u8>>>;
+    // Segment (or partition) the last received event was read from, if the system exposes it.
+    fn segment(&self) -> Option<String> {
+        None
+    }
+    fn close(&mut self) -> DriverResult<()> {
+        Ok(())
+    }
+}
+
+pub fn create_driver(conf: &Config) -> DriverResult<Arc<dyn Driver>> {
+    match conf.driver.as_str() {
+        "pravega" => Ok(Arc::new(PravegaDriver::new(conf.clone()))),
+        "memory"  => Ok(Arc::new(MemoryDriver::new(conf.scale_min_num_segments))),
+        "file"    => Ok(Arc::new(FileDriver::new(conf.clone())?)),
+        #[cfg(feature = "kafka")]
+        "kafka"   => Ok(Arc::new(kafka::KafkaDriver::new(conf.clone())?)),
+        #[cfg(not(feature = "kafka"))]
+        "kafka"   => Err("Driver 'kafka' requires building with --features kafka".into()),
+        #[cfg(feature = "pulsar")]
+        "pulsar"  => Ok(Arc::new(pulsar::PulsarDriver::new(conf.clone())?)),
+        #[cfg(not(feature = "pulsar"))]
+        "pulsar"  => Err("Driver 'pulsar' requires building with --features pulsar".into()),
+        other     => Err(format!("Unknown driver '{}'", other).into()),
+    }
+}
@@ -0,0 +1,334 @@
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::runtime::Handle;
+use std::collections::HashMap;
+use crate::config::Config;
+use pravega_client_shared::Scope;
+use pravega_client_shared::CToken;
+use pravega_client_shared::Stream;
+use pravega_client_shared::Scaling;
+use pravega_client_shared::Retention;
+use pravega_client_shared::ScaleType;
+use pravega_client::event::EventWriter;
+use pravega_client_shared::ScopedStream;
+use pravega_client_shared::RetentionType;
+use pravega_client::event::reader::EventReader;
+use pravega_client::event::reader::SegmentSlice;
+use pravega_client::event::reader_group::ReaderGroup;
+use pravega_client_shared::StreamConfiguration;
+use pravega_client_config::ClientConfigBuilder;
+use pravega_client::client_factory::ClientFactory;
+use super::{Driver, Producer, Consumer, DriverResult};
+
+pub struct PravegaDriver {
+    conf:           Config,
+    client_factory: ClientFactory,
+    // Reader groups are created once, every further consumer joins as a new reader.
+    reader_groups:  Mutex<HashMap<String, Arc<ReaderGroup>>>,
+    // Whether create_topic created the scope, so cleanup only removes scopes of this run.
+    created_scope:  AtomicBool,
+}
+
+pub struct PravegaProducer {
+    handle: Handle,
+    writer: EventWriter,
+    // Own client when separate_clients is set, dropped after the writer.
+    _client_factory: Option<ClientFactory>,
+}
+
+pub struct PravegaConsumer {
+    handle:  Handle,
+    reader:  EventReader,
+    slice:   Option<SegmentSlice>,
+    // Segment of the slice the last event was taken from.
+    segment: Option<String>,
+}
+
+impl PravegaDriver {
+    pub fn new(conf: Config) -> Self {
+        let client_factory = Self::create_client(conf.address.clone());
+        PravegaDriver {
+            conf,
+            client_factory,
+            reader_groups: Mutex::new(HashMap::new()),
+            created_scope: AtomicBool::new(false),
+        }
+    }
+
+    fn create_client(address: String) -> ClientFactory {
+        let pravega_conf = ClientConfigBuilder::default()
+            .controller_uri( address )
+            .build()
+            .unwrap();
+        ClientFactory::new(pravega_conf)
+    }
+
+    fn created_groups(&self) -> Vec<String> {
+        self.reader_groups
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .keys()
+            .cloned()
+            .collect()
+    }
+
+    /*
+     * Reader groups keep their state in table segments of the scope that the
+     * controller does not list, so they are deleted by name before the scope.
+     * Deleting a group that does not exist succeeds.
+     */
+    async fn delete_reader_groups(&self, scope: &Scope, groups: Vec<String>) -> DriverResult<()> {
+        for group in groups {
+            self.client_factory.delete_reader_group(scope.clone(), group).await?;
+        }
+        Ok(())
+    }
+
+    fn get_scoped_stream(&self) -> ScopedStream {
+        ScopedStream {
+            scope:  Scope::from( self.conf.scope.to_owned() ),
+            stream: Stream::from( self.conf.stream.to_owned() ),
+        }
+    }
+
+    fn get_stream_config(&self) -> StreamConfiguration {
+        StreamConfiguration {
+            scoped_stream: self.get_scoped_stream(),
+            scaling: Scaling {
+                scale_type:       self.get_scale_type(),
+                target_rate:      self.conf.scale_target_rate,
+                scale_factor:     self.conf.scale_factor,
+                min_num_segments: self.conf.scale_min_num_segments,
+            },
+            retention: self.get_retention(),
+            tags: None,
+        }
+    }
+
+    // Fixed streams keep scale_min_num_segments segments, the others scale on scale_target_rate.
+    fn get_scale_type(&self) -> ScaleType {
+        match self.conf.scale_type.as_str() {
+            "FixedNumSegments"     => ScaleType::FixedNumSegments,
+            "ByRateInKbytesPerSec" => ScaleType::ByRateInKbytesPerSec,
+            _                      => ScaleType::ByRateInEventsPerSec,
+        }
+    }
+
+    // Time retention is configured in seconds, Pravega expects milliseconds.
+    fn get_retention(&self) -> Retention {
+        match self.conf.retention_type.as_str() {
+            "time" => Retention {
+                retention_type:  RetentionType::Time,
+                retention_param: self.conf.retention_time * 1000,
+            },
+            "size" => Retention {
+                retention_type:  RetentionType::Size,
+                retention_param: self.conf.retention_size,
+            },
+            _ => Retention {
+                retention_type:  RetentionType::None,
+                retention_param: 0,
+            },
+        }
+    }
+}
+
+impl Driver for PravegaDriver {
+    fn name(&self) -> &str {
+        "pravega"
+    }
+
+    fn create_topic(&self) -> DriverResult<()> {
+        self.client_factory.runtime().block_on(async {
+            let controller_client = self.client_factory.controller_client();
+            let scope = Scope::from(self.conf.scope.to_owned());
+            let created = controller_client.create_scope(&scope).await?;
+            self.created_scope.store(created, Ordering::SeqCst);
+            controller_client.create_stream(&self.get_stream_config()).await?;
+            Ok(())
+        })
+    }
+
+    fn create_producer(&self) -> DriverResult<Box<dyn Producer>> {
+        if self.conf.separate_clients {
+            let client_factory = Self::create_client(self.conf.address.clone());
+            return Ok(Box::new(PravegaProducer {
+                handle:          client_factory.runtime_handle(),
+                writer:          client_factory.create_event_writer(self.get_scoped_stream()),
+                _client_factory: Some(client_factory),
+            }));
+        }
+        Ok(Box::new(PravegaProducer {
+            handle:          self.client_factory.runtime_handle(),
+            writer:          self.client_factory.create_event_writer(self.get_scoped_stream()),
+            _client_factory: None,
+        }))
+    }
+
+    fn create_consumer(&self, group: &str, reader: &str) -> DriverResult<Box<dyn Consumer>> {
+        let rg = {
+            let mut reader_groups = self.reader_groups.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            match reader_groups.get(group) {
+                Some(rg) => Arc::clone(rg),
+                None     => {
+                    let rg = Arc::new(self.client_factory.runtime().block_on(
+                        self.client_factory.create_reader_group(group.to_string(), self.get_scoped_stream())
+                    ));
+                    reader_groups.insert(group.to_string(), Arc::clone(&rg));
+                    rg
+                }
+            }
+        };
+        let reader = self.client_factory.runtime().block_on(rg.create_reader(reader.to_string()));
+        Ok(Box::new(PravegaConsumer {
+            handle:  self.client_factory.runtime_handle(),
+            reader,
+            slice:   None,
+            segment: None,
+        }))
+    }
+    fn delete_topic(&self) -> DriverResult<()> {
+        let scope = Scope::from(self.conf.scope.to_owned());
+        self.client_factory.runtime().block_on(async {
+            self.delete_reader_groups(&scope, self.created_groups()).await?;
+            let controller_client = self.client_factory.controller_client();
+            let stream = self.get_scoped_stream();
+            controller_client.seal_stream(&stream).await?;
+            controller_client.delete_stream(&stream).await?;
+            if self.created_scope.load(Ordering::SeqCst) {
+                controller_client.delete_scope(&scope).await?;
+            }
+            Ok(())
+        })
+    }
+
+    fn list_scopes(&self) -> DriverResult<Vec<String>> {
+        self.client_factory.runtime().block_on(async {
+            let controller_client = self.client_factory.controller_client();
+            let mut scopes = Vec::new();
+            let mut token  = CToken::empty();
+            while let Some((page, next)) = controller_client.list_scopes(&token).await? {
+                if page.is_empty() {
+                    break;
+                }
+                scopes.extend(page.into_iter().map(|scope| scope.name));
+                token = next;
+            }
+            Ok(scopes)
+        })
+    }
+
+    fn delete_groups(&self) -> DriverResult<()> {
+        let scope = Scope::from(self.conf.scope.to_owned());
+        self.client_factory.runtime().block_on(self.delete_reader_groups(&scope, self.created_groups()))
+    }
+
+    // The reader groups of old runs are not known, so every name the benchmark uses is tried.
+    fn delete_scope(&self, scope: &str) -> DriverResult<usize> {
+        let scope = Scope::from(scope.to_owned());
+        self.client_factory.runtime().block_on(async {
+            self.delete_reader_groups(&scope, self.conf.reader_group_names()).await?;
+            let controller_client = self.client_factory.controller_client();
+            let mut streams = Vec::new();
+            let mut token   = CToken::empty();
+            while let Some((page, next)) = controller_client.list_streams(&scope, &token).await? {
+                if page.is_empty() {
+                    break;
+                }
+                streams.extend(page);
+                token = next;
+            }
+            for stream in &streams {
+                controller_client.seal_stream(stream).await?;
+                controller_client.delete_stream(stream).await?;
+            }
+            controller_client.delete_scope(&scope).await?;
+            Ok(streams.len())
+        })
+    }
+
+    fn segment_count(&self) -> DriverResult<Option<usize>> {
+        self.client_factory.runtime().block_on(async {
+            let controller_client = self.client_factory.controller_client();
+            let segments = controller_client.get_current_segments(&self.get_scoped_stream()).await?;
+            Ok(Some(segments.key_segment_map.len()))
+        })
+    }
+
+    /*
+     * The head starts past offset 0 of a segment, or in segments of a later
+     * epoch once whole segments were truncated after a scaling.
+     */
+    fn head_truncated(&self) -> DriverResult<Option<bool>> {
+        self.client_factory.runtime().block_on(async {
+            let controller_client = self.client_factory.controller_client();
+            let head = controller_client.get_head_segments(&self.get_scoped_stream()).await?;
+            Ok(Some(head.iter().any(|(segment, &offset)| offset > 0 || segment.get_epoch() > 0)))
+        })
+    }
+}
+
+impl Producer for PravegaProducer {
+    fn send(&mut self, routing_key: Option<String>, payload: Vec<u8>) -> DriverResult<()> {
+        let writer = &mut self.writer;
+        self.handle.block_on(async {
+            let ack = match routing_key {
+                Some(key) => writer.write_event_by_routing_key(key, payload).await,
+                None      => writer.write_event(payload).await,
+            };
+            ack.await??;
+            Ok(())
+        })
+    }
+
+    fn close(&mut self) -> DriverResult<()> {
+        let writer = &mut self.writer;
+        self.handle.block_on(async { writer.flush().await })?;
+        Ok(())
+    }
+}
+
+impl Consumer for PravegaConsumer {
+    /*
+     * Walks the acquired segment slice event by event; once it is exhausted the
+     * slice is released and the next one acquired. The stream is considered
+     * drained when the reader has no more segments to hand out.
+     */
+    fn receive(&mut self) -> DriverResult<Option<Vec<u8>>> {
+        let reader  = &mut self.reader;
+        let slice   = &mut self.slice;
+        let segment = &mut self.segment;
+        self.handle.block_on(async {
+            loop {
+                if let Some(current) = slice.as_mut() {
+                    if let Some(event) = current.next() {
+                        *segment = Some(current.meta.scoped_segment.clone());
+                        return Ok(Some(event.value));
+                    }
+                    reader.release_segment(slice.take().unwrap()).await?;
+                }
+                match reader.acquire_segment().await? {
+                    Some(new_slice) => *slice = Some(new_slice),
+                    None            => return Ok(None),
+                }
+            }
+        })
+    }
+
+    fn segment(&self) -> Option<String> {
+        self.segment.clone()
+    }
+
+    fn close(&mut self) -> DriverResult<()> {
+        let reader = &mut self.reader;
+        let slice  = self.slice.take();
+        self.handle.block_on(async {
+            if let Some(slice) = slice {
+                reader.release_segment(slice).await?;
+            }
+            reader.reader_offline().await?;
+            Ok(())
+        })
+    }
+}
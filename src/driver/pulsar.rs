@@ -0,0 +1,258 @@
+use std::io::Read;
+use std::io::Write;
+use std::time::Duration;
+use std::time::Instant;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::net::TcpStream;
+use futures::StreamExt;
+use crate::config::Config;
+use pulsar::{Pulsar, TokioExecutor, SubType, ConsumerOptions};
+use pulsar::consumer::InitialPosition;
+use tokio::runtime::{Handle, Runtime};
+use super::{Driver, Producer, Consumer, DriverResult, FIRST_EVENT_TIMEOUT, IDLE_TIMEOUT};
+
+// Port of the broker admin REST API when no admin_address is configured.
+const DEFAULT_ADMIN_PORT: u16 = 8080;
+
+pub struct PulsarDriver {
+    conf:              Config,
+    topic:             String,
+    admin:             String,
+    runtime:           Runtime,
+    client:            Pulsar<TokioExecutor>,
+    // Whether create_topic created the namespace.
+    created_namespace: AtomicBool,
+    open_producers:    Arc<AtomicUsize>,
+}
+
+pub struct PulsarProducer {
+    handle:         Handle,
+    producer:       pulsar::Producer<TokioExecutor>,
+    open_producers: Arc<AtomicUsize>,
+    closed:         bool,
+}
+
+pub struct PulsarConsumer {
+    handle:         Handle,
+    consumer:       pulsar::Consumer<Vec<u8>, TokioExecutor>,
+    // Partition topic the last event was read from.
+    topic:          Option<String>,
+    last_event:     Option<Instant>,
+    created:        Instant,
+    open_producers: Arc<AtomicUsize>,
+}
+
+impl PulsarDriver {
+    pub fn new(conf: Config) -> DriverResult<Self> {
+        let runtime = Runtime::new()?;
+        let client  = runtime.block_on(Pulsar::builder(conf.address.clone(), TokioExecutor).build())?;
+        let admin   = if conf.admin_address.is_empty() {
+            Self::default_admin_address(&conf.address)
+        } else {
+            conf.admin_address.clone()
+        };
+        Ok(PulsarDriver {
+            topic: format!("persistent://{}/{}/{}", conf.tenant, conf.scope, conf.stream),
+            conf,
+            admin,
+            runtime,
+            client,
+            created_namespace: AtomicBool::new(false),
+            open_producers:    Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    // pulsar://host:6650 -> host:8080
+    fn default_admin_address(address: &str) -> String {
+        let host = address
+            .trim_start_matches("pulsar://")
+            .split(':')
+            .next()
+            .unwrap_or("localhost");
+        format!("{}:{}", host, DEFAULT_ADMIN_PORT)
+    }
+
+    /*
+     * Minimal HTTP/1.1 request against the admin REST API, which is all we need
+     * to create and delete namespaces and topics. 409 (already exists) and 404
+     * (already gone) are fine, the result tells whether anything was changed.
+     */
+    fn admin_request(&self, method: &str, path: &str, body: &str) -> DriverResult<bool> {
+        let mut stream = TcpStream::connect(&self.admin)?;
+        let request = format!(
+            "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            method, path, self.admin, body.len(), body
+        );
+        stream.write_all(request.as_bytes())?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response)?;
+        let status = response.split_whitespace().nth(1).unwrap_or("");
+        match status {
+            "200" | "204" => Ok(true),
+            "404" | "409" => Ok(false),
+            _ => Err(format!("Admin request {} failed: {}", path, response.lines().next().unwrap_or("")).into()),
+        }
+    }
+}
+
+impl Driver for PulsarDriver {
+    fn name(&self) -> &str {
+        "pulsar"
+    }
+
+    fn create_topic(&self) -> DriverResult<()> {
+        let created = self.admin_request("PUT", &format!("/admin/v2/namespaces/{}/{}", self.conf.tenant, self.conf.scope), "")?;
+        self.created_namespace.store(created, Ordering::SeqCst);
+        if self.conf.scale_min_num_segments > 1 {
+            let path = format!(
+                "/admin/v2/persistent/{}/{}/{}/partitions",
+                self.conf.tenant, self.conf.scope, self.conf.stream
+            );
+            self.admin_request("PUT", &path, &self.conf.scale_min_num_segments.to_string())?;
+        }
+        Ok(())
+    }
+
+    fn delete_topic(&self) -> DriverResult<()> {
+        let mut path = format!("/admin/v2/persistent/{}/{}/{}", self.conf.tenant, self.conf.scope, self.conf.stream);
+        if self.conf.scale_min_num_segments > 1 {
+            path.push_str("/partitions");
+        }
+        self.admin_request("DELETE", &format!("{}?force=true", path), "")?;
+        if self.created_namespace.load(Ordering::SeqCst) {
+            self.admin_request("DELETE", &format!("/admin/v2/namespaces/{}/{}", self.conf.tenant, self.conf.scope), "")?;
+        }
+        Ok(())
+    }
+
+    // Subscriptions keep the backlog of the topic, so they are removed with their cursors.
+    fn delete_groups(&self) -> DriverResult<()> {
+        for subscription in 0..self.conf.subscriptions as usize {
+            let path = format!(
+                "/admin/v2/persistent/{}/{}/{}/subscription/{}?force=true",
+                self.conf.tenant, self.conf.scope, self.conf.stream, self.conf.reader_group_name(subscription)
+            );
+            self.admin_request("DELETE", &path, "")?;
+        }
+        Ok(())
+    }
+
+    fn create_producer(&self) -> DriverResult<Box<dyn Producer>> {
+        let producer = self.runtime.block_on(
+            self.client
+                .producer()
+                .with_topic(self.topic.clone())
+                .build()
+        )?;
+        self.open_producers.fetch_add(1, Ordering::SeqCst);
+        Ok(Box::new(PulsarProducer {
+            handle:         self.runtime.handle().clone(),
+            producer,
+            open_producers: Arc::clone(&self.open_producers),
+            closed:         false,
+        }))
+    }
+
+    // Key_Shared hands every key to a single consumer, so events of a key keep their order like in a Pravega reader group.
+    fn create_consumer(&self, group: &str, reader: &str) -> DriverResult<Box<dyn Consumer>> {
+        let consumer = self.runtime.block_on(
+            self.client
+                .consumer()
+                .with_topic(self.topic.clone())
+                .with_subscription(group)
+                .with_subscription_type(SubType::KeyShared)
+                .with_consumer_name(reader)
+                .with_options(ConsumerOptions::default().with_initial_position(InitialPosition::Earliest))
+                .build::<Vec<u8>>()
+        )?;
+        Ok(Box::new(PulsarConsumer {
+            handle:         self.runtime.handle().clone(),
+            consumer,
+            topic:          None,
+            last_event:     None,
+            created:        Instant::now(),
+            open_producers: Arc::clone(&self.open_producers),
+        }))
+    }
+}
+
+impl Producer for PulsarProducer {
+    fn send(&mut self, routing_key: Option<String>, payload: Vec<u8>) -> DriverResult<()> {
+        let producer = &mut self.producer;
+        self.handle.block_on(async {
+            let mut message = producer.create_message().with_content(payload);
+            if let Some(key) = routing_key {
+                message = message.with_key(key);
+            }
+            message.send_non_blocking().await?.await?;
+            Ok(())
+        })
+    }
+
+    fn close(&mut self) -> DriverResult<()> {
+        if !self.closed {
+            let producer = &mut self.producer;
+            self.handle.block_on(producer.close())?;
+            self.closed = true;
+            self.open_producers.fetch_sub(1, Ordering::SeqCst);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for PulsarProducer {
+    fn drop(&mut self) {
+        if !self.closed {
+            self.open_producers.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
+
+impl Consumer for PulsarConsumer {
+    /*
+     * Pulsar topics have no end either: the topic is drained once every producer
+     * is closed and no event arrived for a while. With Key_Shared the consumers of
+     * a subscription do not read up to a common position, so unlike Kafka there
+     * is no watermark to wait for.
+     */
+    fn receive(&mut self) -> DriverResult<Option<Vec<u8>>> {
+        let consumer       = &mut self.consumer;
+        let topic          = &mut self.topic;
+        let last_event     = &mut self.last_event;
+        let created        = self.created;
+        let open_producers = &self.open_producers;
+        self.handle.block_on(async {
+            loop {
+                match tokio::time::timeout(Duration::from_millis(100), consumer.next()).await {
+                    Ok(Some(message)) => {
+                        let message = message?;
+                        consumer.ack(&message).await?;
+                        *last_event = Some(Instant::now());
+                        *topic      = Some(message.topic.clone());
+                        return Ok(Some(message.payload.data));
+                    },
+                    Ok(None) => return Ok(None),
+                    Err(_)   => {},
+                }
+                let idle = match last_event {
+                    Some(last) => last.elapsed() > IDLE_TIMEOUT,
+                    None       => created.elapsed() > FIRST_EVENT_TIMEOUT,
+                };
+                if idle && open_producers.load(Ordering::SeqCst) == 0 {
+                    return Ok(None);
+                }
+            }
+        })
+    }
+
+    fn segment(&self) -> Option<String> {
+        self.topic.clone()
+    }
+
+    fn close(&mut self) -> DriverResult<()> {
+        let consumer = &mut self.consumer;
+        self.handle.block_on(consumer.close())?;
+        Ok(())
+    }
+}
@@ -0,0 +1,140 @@
+use chrono::DateTime;
+use crc32fast::Hasher;
+use chrono::prelude::Utc;
+
+// Marks payloads stamped by this benchmark, so foreign events are not misread.
+const MAGIC: [u8; 4] = *b"PRBH";
+// Marks payloads that also carry the verification fields.
+const VERIFY_MAGIC: [u8; 4] = *b"PRBV";
+pub const HEADER_LEN: usize = 24;
+pub const VERIFY_HEADER_LEN: usize = 36;
+// Routing key index stamped on events written without a routing key.
+pub const NO_KEY: u32 = u32::MAX;
+
+/*
+ * Header written over the first bytes of every payload right before it is sent:
+ * magic, sequence number, send timestamp in microseconds and event size
+ * (big-endian), so the message size does not change and readers can check it.
+ * Payloads shorter than the header are sent as is.
+ *
+ * In verification mode the sequence number counts the events of each writer and
+ * the header continues with the writer id, the routing key index and a CRC32 of
+ * the whole payload except the checksum field itself.
+ */
+pub struct Header {
+    pub sequence:     u64,
+    pub timestamp:    i64,
+    pub size:         u32,
+    pub verification: Option<Verification>,
+}
+
+pub struct Verification {
+    pub writer:   u32,
+    pub key:      u32,
+    pub checksum: u32,
+}
+
+impl Header {
+    pub fn new(sequence: u64, time: DateTime<Utc>) -> Header {
+        Header { sequence, timestamp: time.timestamp_micros(), size: 0, verification: None }
+    }
+
+    pub fn stamp(&self, payload: &mut [u8]) {
+        if payload.len() < HEADER_LEN {
+            return;
+        }
+        payload[0..4].copy_from_slice(&MAGIC);
+        payload[4..12].copy_from_slice(&self.sequence.to_be_bytes());
+        payload[12..20].copy_from_slice(&self.timestamp.to_be_bytes());
+        let size = payload.len() as u32;
+        payload[20..24].copy_from_slice(&size.to_be_bytes());
+    }
+
+    // Stamps the verification header, the checksum is computed over the stamped payload.
+    pub fn stamp_verified(&self, writer: u32, key: u32, payload: &mut [u8]) {
+        if payload.len() < VERIFY_HEADER_LEN {
+            return;
+        }
+        self.stamp(payload);
+        payload[0..4].copy_from_slice(&VERIFY_MAGIC);
+        payload[24..28].copy_from_slice(&writer.to_be_bytes());
+        payload[28..32].copy_from_slice(&key.to_be_bytes());
+        let checksum = Self::checksum(payload);
+        payload[32..36].copy_from_slice(&checksum.to_be_bytes());
+    }
+
+    pub fn parse(payload: &[u8]) -> Option<Header> {
+        if payload.len() < HEADER_LEN {
+            return None;
+        }
+        let verification = if payload[0..4] == VERIFY_MAGIC && payload.len() >= VERIFY_HEADER_LEN {
+            Some(Verification {
+                writer:   u32::from_be_bytes(payload[24..28].try_into().unwrap()),
+                key:      u32::from_be_bytes(payload[28..32].try_into().unwrap()),
+                checksum: u32::from_be_bytes(payload[32..36].try_into().unwrap()),
+            })
+        } else if payload[0..4] == MAGIC {
+            None
+        } else {
+            return None;
+        };
+        Some(Header {
+            sequence:  u64::from_be_bytes(payload[4..12].try_into().unwrap()),
+            timestamp: i64::from_be_bytes(payload[12..20].try_into().unwrap()),
+            size:      u32::from_be_bytes(payload[20..24].try_into().unwrap()),
+            verification,
+        })
+    }
+
+    // CRC32 of a verified payload, skipping the checksum field.
+    pub fn checksum(payload: &[u8]) -> u32 {
+        let mut hasher = Hasher::new();
+        hasher.update(&payload[0..32]);
+        hasher.update(&payload[VERIFY_HEADER_LEN..]);
+        hasher.finalize()
+    }
+
+    pub fn time(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp_micros(self.timestamp).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stamp_and_parse_round_trip() {
+        let time = DateTime::from_timestamp_micros(1_700_000_000_123_456).unwrap();
+        let mut payload = vec![7u8; 100];
+        Header::new(42, time).stamp(&mut payload);
+        let header = Header::parse(&payload).unwrap();
+        assert_eq!(header.sequence, 42);
+        assert_eq!(header.time(), time);
+        assert_eq!(header.size, 100);
+        assert!(header.verification.is_none());
+        assert_eq!(payload[HEADER_LEN..], [7u8; 100 - HEADER_LEN]);
+    }
+
+    #[test]
+    fn verified_stamp_carries_writer_key_and_checksum() {
+        let mut payload = vec![1u8; 64];
+        Header::new(3, Utc::now()).stamp_verified(2, 5, &mut payload);
+        let header = Header::parse(&payload).unwrap();
+        let verification = header.verification.unwrap();
+        assert_eq!(header.sequence, 3);
+        assert_eq!((verification.writer, verification.key), (2, 5));
+        assert_eq!(verification.checksum, Header::checksum(&payload));
+        payload[50] ^= 1;
+        assert_ne!(verification.checksum, Header::checksum(&payload));
+    }
+
+    #[test]
+    fn short_and_foreign_payloads_are_not_parsed() {
+        let mut short = vec![1u8; HEADER_LEN - 1];
+        Header::new(1, Utc::now()).stamp(&mut short);
+        assert_eq!(short, vec![1u8; HEADER_LEN - 1]);
+        assert!(Header::parse(&short).is_none());
+        assert!(Header::parse(&[0u8; 64]).is_none());
+    }
+}
@@ -0,0 +1,162 @@
+use serde::Serialize;
+use serde::Deserialize;
+use hdrhistogram::Histogram;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use hdrhistogram::serialization::Serializer;
+use hdrhistogram::serialization::V2DeflateSerializer;
+
+// Highest trackable latency (1 hour in microseconds), the histogram grows past it if needed.
+const HIGHEST_LATENCY: u64 = 3_600_000_000;
+const SIGNIFICANT_DIGITS: u8 = 3;
+
+#[derive(Serialize, Deserialize)]
+pub struct PercentileRow {
+    pub percentile:  f64,
+    pub value:       f64,
+    pub total_count: u64,
+}
+
+/*
+ * Latencies are given and reported in milliseconds but recorded with
+ * microsecond resolution in an HdrHistogram, so memory stays bounded no
+ * matter how many messages the run sends.
+ */
+pub struct LatencyHistogram {
+    histogram: Histogram<u64>,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        let mut histogram = Histogram::new_with_bounds(1, HIGHEST_LATENCY, SIGNIFICANT_DIGITS).unwrap();
+        histogram.auto(true);
+        LatencyHistogram { histogram }
+    }
+}
+
+impl LatencyHistogram {
+    /*
+     * Small histogram for the time-series intervals, with two significant
+     * digits and grown only as far as the recorded latencies need.
+     */
+    pub fn compact() -> Self {
+        let mut histogram = Histogram::new_with_bounds(1, 2, 2).unwrap();
+        histogram.auto(true);
+        LatencyHistogram { histogram }
+    }
+
+    // Grows the histogram for large values, saturating_record alone would clamp them.
+    pub fn record(&mut self, millis: f64) {
+        let micros = (millis * 1000.0).round().max(0.0) as u64;
+        if self.histogram.record(micros).is_err() {
+            self.histogram.saturating_record(micros);
+        }
+    }
+
+    pub fn len(&self) -> u64 {
+        self.histogram.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.histogram.is_empty()
+    }
+
+    pub fn percentile(&self, percentile: f64) -> f64 {
+        Self::to_millis(self.histogram.value_at_quantile(percentile / 100.0))
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.histogram.mean() / 1000.0
+    }
+
+    pub fn max(&self) -> f64 {
+        Self::to_millis(self.histogram.max())
+    }
+
+    // Base64 of the compressed V2 encoding, readable by the HdrHistogram tools of any language.
+    pub fn serialize(&self) -> String {
+        let mut buffer = Vec::new();
+        if V2DeflateSerializer::new().serialize(&self.histogram, &mut buffer).is_err() {
+            return String::new();
+        }
+        STANDARD.encode(buffer)
+    }
+
+    // Percentile distribution table, halving the distance to 100% at every row.
+    pub fn distribution(&self) -> Vec<PercentileRow> {
+        let mut total_count = 0;
+        let mut rows: Vec<PercentileRow> = self.histogram.iter_quantiles(1).map(|step| {
+            total_count += step.count_since_last_iteration();
+            PercentileRow {
+                percentile: step.percentile(),
+                value:      Self::to_millis(step.value_iterated_to()),
+                total_count,
+            }
+        }).collect();
+        // The iterator reports the 100% row twice
+        rows.dedup_by(|row, previous| row.total_count == previous.total_count && row.value == previous.value);
+        rows
+    }
+
+    fn to_millis(micros: u64) -> f64 {
+        micros as f64 / 1000.0
+    }
+}
+
+// Sizes of the sent events in bytes, with the same precision as the latencies.
+pub struct SizeHistogram {
+    histogram: Histogram<u64>,
+}
+
+impl Default for SizeHistogram {
+    fn default() -> Self {
+        let mut histogram = Histogram::new_with_bounds(1, 2, SIGNIFICANT_DIGITS).unwrap();
+        histogram.auto(true);
+        SizeHistogram { histogram }
+    }
+}
+
+impl SizeHistogram {
+    pub fn record(&mut self, bytes: u64) {
+        if self.histogram.record(bytes).is_err() {
+            self.histogram.saturating_record(bytes);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.histogram.is_empty()
+    }
+
+    pub fn percentile(&self, percentile: f64) -> u64 {
+        self.histogram.value_at_quantile(percentile / 100.0)
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.histogram.mean()
+    }
+
+    pub fn max(&self) -> u64 {
+        self.histogram.max()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compact_grows_for_large_latencies() {
+        let mut histogram = LatencyHistogram::compact();
+        for _ in 0..90 {
+            histogram.record(0.1);
+        }
+        for _ in 0..10 {
+            histogram.record(5.0);
+        }
+        assert_eq!(histogram.len(), 100);
+        // Two significant digits keep the values within 1%
+        assert!((histogram.percentile(50.0) - 0.1).abs() < 0.001);
+        assert!((histogram.percentile(99.0) - 5.0).abs() < 0.05);
+        assert!((histogram.max() - 5.0).abs() < 0.05);
+    }
+}
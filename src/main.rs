@@ -1,35 +1,81 @@
 mod config;
+mod driver;
+mod rate;
+mod header;
+mod verify;
+mod payload;
+mod size;
+mod routing;
+mod retention;
+mod cleanup;
+mod counters;
 mod result;
+mod metrics;
+mod reporter;
+mod histogram;
 mod channel_data;
 
 use std::env;
 use std::thread;
 use std::process;
+use std::collections::HashMap;
 
 use config::Config;
+use header::Header;
+use rate::RateSchedule;
+use counters::Counters;
+use verify::Verifier;
+use payload::Payloads;
+use size::SizeDistribution;
 use std::sync::Arc;
-use async_std::task;
 use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use chrono::DateTime;
 use result::TestResult;
+use metrics::Metrics;
+use reporter::Reporter;
 use std::time::Duration;
+use std::time::Instant;
 use chrono::prelude::Utc;
 use threadpool::ThreadPool;
 use channel_data::ChannelData;
-use pravega_client_shared::Scope;
-use pravega_client_shared::Stream;
-use pravega_client_shared::Scaling;
-use pravega_client_shared::Retention;
-use pravega_client_shared::ScaleType;
-use pravega_client::event::EventWriter;
-use pravega_client_shared::ScopedStream;
-use pravega_client_shared::RetentionType;
+use driver::{Driver, Producer};
 use std::sync::mpsc::{self, RecvTimeoutError};
-use pravega_client_shared::StreamConfiguration;
-use pravega_client_config::ClientConfigBuilder;
-use pravega_client::client_factory::ClientFactory;
 
 const START_CONSTANT: i32 = 95;
+const LAG_SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+// How often the segment poller checks whether the benchmark threads are done.
+const POLL_STOP_CHECK: Duration = Duration::from_millis(50);
+// How often every reader prints how many events its group read.
+const READ_PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/*
+ * A producer and the number of events it stamped, behind one lock so the
+ * verification sequence numbers follow the order the events are sent in.
+ */
+struct Writer {
+    id:       u32,
+    producer: Box<dyn Producer>,
+    stamped:  u64,
+}
+
+type SharedProducer = Arc<Mutex<Writer>>;
+
+// Counts a benchmark thread as running until it ends, also when it panics.
+struct RunningGuard(Arc<AtomicUsize>);
+
+impl RunningGuard {
+    fn new(running: &Arc<AtomicUsize>) -> RunningGuard {
+        running.fetch_add(1, Ordering::SeqCst);
+        RunningGuard(Arc::clone(running))
+    }
+}
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     env::set_var("RUST_BACKTRACE", "1");
@@ -38,204 +84,405 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args: Vec<String> = env::args().collect();
     if args.len() <= 1 {
         println!("Arguments missing.\nUsage: {} <Yaml config file>", args[0]);
+        println!("       {} cleanup <Yaml config file> [max age in hours] [scope prefix] [--dry-run]", args[0]);
         process::exit(1);
     }
+    if args[1] == "cleanup" {
+        return cleanup_command(&args);
+    }
     
     // Getting config and payload content
     let conf = Config::load_from_file(&args[1].clone()).expect("Could not read config file.");
 
+    let driver    = driver::create_driver(&conf).expect("Could not create driver.");
+    let run_start = Instant::now();
+    let counters  = Arc::new(Counters::new(&conf));
+    let reporter  = Reporter::new(&conf, Arc::clone(&counters));
+    let progress  = reporter.start();
+    let metrics   = Metrics::new();
+    if !conf.metrics_address.is_empty() {
+        metrics.serve(&conf.metrics_address).expect("Could not start metrics endpoint.");
+    }
+
     // Starting Threads
     let (tx1, rx1) = mpsc::channel(); // Start Signal
     let (tx2, rx2) = mpsc::channel(); // Latencies
 
-    let tx3 = tx2.clone();
-    let config_cpy = conf.clone();
-    let handler_snd = thread::spawn(move || {
-        sender_handler(tx1, tx2, config_cpy);
-    });
+    // The segment poller starts with the benchmark and runs until the threads below are done
+    let mut poll_out = (conf.segment_poll_interval > 0).then(|| tx2.clone());
+    let running      = Arc::new(AtomicUsize::new(0));
+    let mut poller   = None;
 
-    let config_cpy = conf.clone();
-    let handler_rcv = thread::spawn(move || { 
-        receiver_handler(rx1, tx3, config_cpy);
-    });
+    // Only the threads of the mode run, a read-only run starts reading right away
+    let tx3 = tx2.clone();
+    let handler_snd = if conf.mode == "read_only" {
+        attach_stream(tx1, tx2, &conf, driver.as_ref(), &counters);
+        None
+    } else {
+        let config_cpy   = conf.clone();
+        let driver_cpy   = Arc::clone(&driver);
+        let counters_cpy = Arc::clone(&counters);
+        let guard        = RunningGuard::new(&running);
+        Some(thread::spawn(move || {
+            let _guard = guard;
+            sender_handler(tx1, tx2, config_cpy, driver_cpy, counters_cpy);
+        }))
+    };
 
-    match handler_snd.join() {
-        Ok(_)  => println!("\t + Writing finished"),
-        Err(e) => println!("\t + Thread panicked: {:?}", e),
-    }
-    match handler_rcv.join() {
-        Ok(_)  => println!("\t - Reading finished"),
-        Err(e) => println!("\t - Thread panicked: {:?}", e),
-    }
+    let handler_rcv = if conf.mode == "write_only" {
+        drop(tx3);
+        None
+    } else {
+        let config_cpy   = conf.clone();
+        let driver_cpy   = Arc::clone(&driver);
+        let counters_cpy = Arc::clone(&counters);
+        let guard        = RunningGuard::new(&running);
+        Some(thread::spawn(move || {
+            let _guard = guard;
+            receiver_handler(rx1, tx3, config_cpy, driver_cpy, counters_cpy);
+        }))
+    };
 
-    // get ouput data from threads
-    let mut result = TestResult::new(conf);
-    println!("\t i Receiving data from threads");
+    /*
+     * Get output data from threads while they run, so the channel does not
+     * pile up and the progress reporter sees current latencies. Timeouts only
+     * count once both threads are gone.
+     */
+    let mut result = TestResult::new(conf.clone());
     let mut errors = 0;
+    let mut stream_created = None;
+    let finished = |handler: &Option<thread::JoinHandle<()>>| handler.as_ref().is_none_or(|handler| handler.is_finished());
     while errors < 60 {
         let item = rx2.recv_timeout(Duration::from_secs(1));
         match item {
-            Ok(ChannelData::WriteLatency(value))  => result.add_write_latency(value),
-            Ok(ChannelData::ReadLatency(value))   => result.add_read_latency(value),
-            Ok(ChannelData::WriteDuration(value)) => result.set_duration(value),
-            Err(RecvTimeoutError::Timeout)        => {errors += 1; continue;},
-            Err(RecvTimeoutError::Disconnected)   => break
+            Ok(ChannelData::StartTime(value))            => {
+                result.set_start_time(value);
+                if let Some(out) = poll_out.take() {
+                    let interval = Duration::from_millis(conf.segment_poll_interval);
+                    let driver   = Arc::clone(&driver);
+                    let running  = Arc::clone(&running);
+                    poller = Some(thread::spawn(move || poll_segments(driver, interval, running, out)));
+                }
+            },
+            Ok(ChannelData::StreamCreated(at))           => stream_created = Some(at),
+            Ok(ChannelData::WriteLatency(id, value, corrected, at, bytes)) => {
+                reporter.record_write(value);
+                metrics.record_write(value, bytes);
+                result.add_write_latency(id, value, corrected, at, bytes)
+            },
+            Ok(ChannelData::ReadLatency(id, value, at, bytes)) => {
+                metrics.record_read(value, bytes);
+                result.add_read_latency(id, value, at, bytes)
+            },
+            Ok(ChannelData::WriteDuration(value))        => result.set_duration(value),
+            Ok(ChannelData::ProducerDuration(id, value)) => result.set_producer_duration(id, value),
+            Ok(ChannelData::ConsumerDuration(id, value)) => result.set_consumer_duration(id, value),
+            Ok(ChannelData::SubscriptionLag(id, value))  => result.add_subscription_lag(id, value),
+            Ok(ChannelData::Verification(value))         => result.add_verification(value),
+            Ok(ChannelData::SegmentEvents(id, value))    => result.add_segment_events(id, value),
+            Ok(ChannelData::SegmentCount(at, value))     => result.add_segment_count(at, value),
+            Ok(ChannelData::EndToEndLatency(value))      => {
+                reporter.record_e2e(value);
+                metrics.record_e2e(value);
+                result.add_e2e_latency(value)
+            },
+            Err(RecvTimeoutError::Timeout)               => {
+                if finished(&handler_snd) && finished(&handler_rcv) {
+                    poll_out = None;
+                    errors += 1;
+                }
+            },
+            Err(RecvTimeoutError::Disconnected)          => break
         }
     }
+    reporter.stop();
+    if let Some(progress) = progress {
+        let _ = progress.join();
+    }
+    if let Some(poller) = poller {
+        let _ = poller.join();
+    }
+
+    if let Some(handler_snd) = handler_snd {
+        match handler_snd.join() {
+            Ok(_)  => println!("\t + Writing finished"),
+            Err(e) => println!("\t + Thread panicked: {:?}", e),
+        }
+    }
+    if let Some(handler_rcv) = handler_rcv {
+        match handler_rcv.join() {
+            Ok(_)  => println!("\t - Reading finished"),
+            Err(e) => println!("\t - Thread panicked: {:?}", e),
+        }
+    }
+
+    // A read-only run leaves no reader groups behind on the stream it attached to
+    if conf.mode == "read_only" {
+        if let Err(e) = driver.delete_groups() {
+            println!("\t i Error at deleting the reader groups: {}", e);
+        }
+    }
+
+    // A read-only run does not know when the stream was created, its age counts from the start of the run
+    if conf.retention_check {
+        let written = counters.total_written_bytes();
+        let age     = stream_created.unwrap_or(run_start).elapsed();
+        result.set_retention(retention::check(&conf, driver.as_ref(), written, age));
+    }
+
     println!("\t i Calculating metrics");
     result.calculate_metrics();
     result.to_file().expect("Failed to write results.");
+
+    if conf.cleanup {
+        cleanup::cleanup_run(&conf, driver.as_ref());
+    }
     Ok(())
 }
 
-fn create_client(address: String) -> ClientFactory {
-    let pravega_conf = ClientConfigBuilder::default()
-        .controller_uri( address )
-        .build()
-        .unwrap();
-    let client_factory = ClientFactory::new(pravega_conf);
-    client_factory
+// Deletes the scopes of old runs on the system of the given workload file.
+fn cleanup_command(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let dry_run = args.iter().any(|arg| arg == "--dry-run");
+    let params: Vec<&String> = args[2..].iter().filter(|arg| *arg != "--dry-run").collect();
+    if params.is_empty() {
+        println!("Arguments missing.\nUsage: {} cleanup <Yaml config file> [max age in hours] [scope prefix] [--dry-run]", args[0]);
+        process::exit(1);
+    }
+    let conf    = Config::load_cleanup_from_file(params[0]).expect("Could not read config file.");
+    let max_age = match params.get(1) {
+        Some(hours) => hours.parse::<u64>()?,
+        None        => 24,
+    };
+    let prefix  = params.get(2).map(|prefix| prefix.as_str()).unwrap_or("scope");
+    let driver  = driver::create_driver(&conf).expect("Could not create driver.");
+    println!("Cleaning up {} scopes {}* older than {} hours", driver.name(), prefix, max_age);
+    cleanup::cleanup_scopes(driver.as_ref(), prefix, max_age, dry_run)
 }
 
 fn get_difference(start_time: DateTime<chrono::Utc>, ends_time: DateTime<chrono::Utc>) -> f64 {
     let difference = ends_time - start_time;
-    let time = difference.num_milliseconds() as f64 + (difference.num_microseconds().unwrap() % 1000) as f64 / 1000.0;
-    time
+    difference.num_milliseconds() as f64 + (difference.num_microseconds().unwrap() % 1000) as f64 / 1000.0
 }
 
-fn get_scoped_stream(conf_scope: String, conf_stream: String) -> ScopedStream {
-    let scoped_stream = ScopedStream {
-        scope:  Scope::from( conf_scope ),
-        stream: Stream::from( conf_stream ),
-    };
-    scoped_stream
+// Returns when the event was sent and its latency, -1 if the send failed.
+fn write_one_event(arc_producer: SharedProducer, mut payload: Vec<u8>, sequence: u64, key: Option<u32>, verify: bool) -> Result<(DateTime<Utc>, f64), std::io::Error> {
+    let mut writer = arc_producer.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let start_time = Utc::now();
+    if verify {
+        Header::new(writer.stamped, start_time).stamp_verified(writer.id, key.unwrap_or(header::NO_KEY), &mut payload);
+        writer.stamped += 1;
+    } else {
+        Header::new(sequence, start_time).stamp(&mut payload);
+    }
+    if writer.producer.send(key.map(routing::key_name), payload).is_err() {
+        return Ok((start_time, -1.0))
+    }
+
+    let end_time = Utc::now();
+    let latency = get_difference(start_time, end_time);
+    Ok((start_time, latency))
 }
 
-fn get_stream_config(conf: Config, scope: Scope) -> StreamConfiguration {
-    let stream = Stream::from(conf.stream.to_owned());
-    let stream_config = StreamConfiguration {
-        scoped_stream: ScopedStream {
-            scope:  scope.clone(),
-            stream: stream.clone(),
-        },
-        scaling: Scaling {
-            scale_type:       ScaleType::ByRateInEventsPerSec,
-            target_rate:      conf.scale_target_rate,
-            scale_factor:     conf.scale_factor,
-            min_num_segments: conf.scale_min_num_segments,
-        },
-        /*retention: Retention {
-            retention_type:  RetentionType::Time,
-            retention_param: conf.retention_time,
-        },*/
-        retention: Retention {
-            retention_type:  RetentionType::Size,
-            retention_param: 10485760,
-        },
-        tags: None,
-    };
-    stream_config
+// Share of `total` assigned to the `index`-th of `parts` workers.
+fn split_share(total: u32, parts: u32, index: u32) -> u32 {
+    total / parts + u32::from(index < total % parts)
 }
 
-async fn write_one_event(arc_event_writer: Arc<Mutex<EventWriter>>, payload: Vec<u8>) -> Result<f64, std::io::Error> {
-    let mut event_writer = arc_event_writer.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
-    let start_time = Utc::now();
-    let result = event_writer.write_event(payload).await;
-    if !result.await.is_ok() {
-        return Ok(-1.0)
+/*
+ * Starts the readers of a read-only run on the existing stream, without
+ * creating it and without a warmup, so every event of the stream counts.
+ */
+fn attach_stream(signal: mpsc::Sender<i32>, out: mpsc::Sender<ChannelData>, conf: &Config, driver: &dyn Driver, counters: &Counters) {
+    println!("Configuration {}", conf.name);
+    println!("\t Driver        {}", driver.name());
+    println!("\t EndPoint      {}", conf.address);
+    println!("\t Mode          {}", conf.mode);
+    if conf.read_message_num > 0 {
+        println!("\t Messages      {}", conf.read_message_num);
+    } else {
+        println!("\t Messages      until the end of the stream");
     }
+    println!("\t Scope {} / Stream {}", conf.scope, conf.stream);
+    counters.set_warmup(0);
 
-    let end_time = Utc::now();
-    let latency = get_difference(start_time, end_time);
-    return Ok(latency)
+    println!("Starting Benchmark");
+    out.send(ChannelData::StartTime(Utc::now())).unwrap();
+    signal.send(START_CONSTANT).unwrap();
 }
 
-fn sender_handler(signal: mpsc::Sender<i32>, out: mpsc::Sender<ChannelData>, conf: Config) {
-    let payload = conf.get_payload();
-    let client_factory = create_client(conf.address.clone());
+fn sender_handler(signal: mpsc::Sender<i32>, out: mpsc::Sender<ChannelData>, conf: Config, driver: Arc<dyn Driver>, counters: Arc<Counters>) {
+    let payloads = payload::generate(&conf).expect("Could not generate payloads.");
 
     println!("Configuration {}", conf.name);
+    println!("\t Driver        {}", driver.name());
     println!("\t EndPoint      {}", conf.address);
-    println!("\t WarmUp        {}", conf.message_warmup);
-    println!("\t Payload File  {}", conf.payload_file);
-    println!("\t Messages      {}", conf.message_num);
+    if conf.warmup_duration > 0 {
+        println!("\t WarmUp        {} s", conf.warmup_duration);
+    } else {
+        println!("\t WarmUp        {}", conf.message_warmup);
+    }
+    if conf.payload_type == "file" {
+        println!("\t Payload File  {}", conf.payload_file);
+    } else {
+        println!("\t Payload       {} x {} ({} bytes)", conf.payload_type, payloads.len(), conf.payload_size);
+    }
+    println!("\t Message Size  {}", conf.size_distribution);
+    match conf.routing_key.as_str() {
+        "uniform" | "zipf" => println!("\t Routing Keys  {} over {}", conf.routing_key, conf.routing_key_count),
+        other              => println!("\t Routing Keys  {}", other),
+    }
+    if conf.duration > 0 {
+        println!("\t Duration      {} s", conf.duration);
+    } else {
+        println!("\t Messages      {}", conf.message_num);
+    }
     println!("\t Producer Rate {}", conf.producer_rate);
+    println!("\t Producers     {}", conf.producers);
+    println!("\t Mode          {}", conf.mode);
+    println!("\t Read Mode     {}", conf.read_mode);
     println!("Init Environment");
-    client_factory.runtime().block_on(async {
-        let controller_client = client_factory.controller_client();
-        // create a scope
-        let scope = Scope::from(conf.scope.to_owned());
-        controller_client.create_scope(&scope)
-            .await
-            .expect("create scope");
-        println!("\t Scope {} created", conf.scope);
-
-        // create a stream containing only one segment
-        let stream_config = get_stream_config(conf.clone(), scope.clone());
-        controller_client
-            .create_stream(&stream_config)
-            .await
-            .expect("create stream");
-        println!("\t Stream {} created", conf.stream);
-
-        println!("Starting WarmUp {} messages", conf.message_warmup);
-        let scoped_stream = get_scoped_stream(conf.scope, conf.stream);
-        let event_writer = client_factory.create_event_writer(scoped_stream);
-        let shared_event_writer = Arc::new(Mutex::new(event_writer));
-
-        for i in 1..=conf.message_warmup {
-            let payload          = payload.clone();
-            let arc_event_writer = Arc::clone(&shared_event_writer);
-            let _ = write_one_event(arc_event_writer, payload).await;
-            if i % conf.producer_rate == 0 {
-                thread::sleep(Duration::from_secs(1));
+    driver.create_topic().expect("create topic");
+    out.send(ChannelData::StreamCreated(Instant::now())).unwrap();
+    println!("\t Scope {} created", conf.scope);
+    println!("\t Stream {} created", conf.stream);
+
+    println!("Starting WarmUp");
+    let producers: Vec<SharedProducer> = (0..conf.producers)
+        .map(|id| Arc::new(Mutex::new(Writer {
+            id,
+            producer: driver.create_producer().expect("create producer"),
+            stamped:  0,
+        })))
+        .collect();
+
+    let mut sizes    = conf.size_sampler(0);
+    let mut keys     = conf.key_sampler(0);
+    let mut schedule = RateSchedule::new(conf.producer_rate, &conf.arrival, conf.message_warmup, conf.warmup_duration);
+    while schedule.wait().is_some() {
+        let payload      = payload::sized(&payloads[schedule.sent() as usize % payloads.len()], sizes.sample());
+        let writer       = (schedule.sent() % conf.producers) as usize;
+        let arc_producer = Arc::clone(&producers[writer]);
+        if let Ok((_, latency)) = write_one_event(arc_producer, payload, counters.next_sequence(), keys.sample(), conf.verify) {
+            if latency >= 0.0 {
+                counters.add_acked(writer);
             }
         }
-        
-        println!("Starting Benchmark");
+    }
+    // Readers skip exactly the warmup messages, also when the warmup was timed.
+    println!("\t WarmUp {} messages sent", schedule.sent());
+    counters.set_warmup(schedule.sent());
+
+    println!("Starting Benchmark");
+    if conf.mode == "both" && conf.read_mode == "tail" {
         signal.send(START_CONSTANT).unwrap();
-        
-        /*
-         * Create a thread for each message to send, and when the created threads are
-         * equal to produce rate wait for a second. This ensure the produce rate per second
-         * requirement.
-         */
-        let pool      = ThreadPool::new(conf.producer_rate as usize);
-        let ben_start = Utc::now();
-        for i in 1..=conf.message_num {
-            let out_cloned       = out.clone();
-            let payload_cloned   = payload.clone();
-            let arc_event_writer = Arc::clone(&shared_event_writer);
-            pool.execute(move || {
-                let res = task::block_on( write_one_event(arc_event_writer, payload_cloned) );
-                match res {
-                    Ok(value) => out_cloned.send(ChannelData::WriteLatency(value)).unwrap(),
-                    Err(_) => println!("\t + Error at sending")
-                };
-            });
-            if i % conf.producer_rate == 0 {
-                println!("\t + Messages Sent {}", i);
-                thread::sleep(Duration::from_secs(1));
-            }
-        }
-        if conf.message_num % conf.producer_rate != 0 {
-            println!("\t + Messages Sent {}", conf.message_num);
-            thread::sleep(Duration::from_secs(1));
+    }
+
+    // Every producer sends its share of the messages at its share of the rate.
+    let ben_start = Utc::now();
+    out.send(ChannelData::StartTime(ben_start)).unwrap();
+    let handlers: Vec<_> = producers.iter().enumerate().map(|(id, producer)| {
+        let arc_producer = Arc::clone(producer);
+        let payloads     = Arc::clone(&payloads);
+        let conf         = conf.clone();
+        let out          = out.clone();
+        let counters     = Arc::clone(&counters);
+        thread::spawn(move || {
+            producer_handler(id, arc_producer, payloads, conf, out, counters);
+        })
+    }).collect();
+    for handler in handlers {
+        if let Err(e) = handler.join() {
+            println!("\t + Producer thread panicked: {:?}", e);
         }
+    }
 
-        println!("\t + Waiting the pool to finish");
-        pool.join();
+    // Wait for the producers to finish and calculate the total time for benchmark sending.
+    let ben_ends = Utc::now();
+    let duration = get_difference(ben_start, ben_ends);
+    out.send(ChannelData::WriteDuration(duration)).unwrap();
 
-        // Wait for the threads to finish and calculate the total time for benchmark sending.
-        let ben_ends = Utc::now();
-        let duration = get_difference(ben_start, ben_ends);
-        out.send(ChannelData::WriteDuration(duration)).unwrap();
-    });
+    for producer in &producers {
+        let mut writer = producer.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Err(e) = writer.producer.close() {
+            println!("\t + Error at closing producer: {}", e);
+        }
+    }
+
+    /*
+     * Catch-up reads start from the head of the complete stream once every
+     * event is written, optionally after giving the system time to move the
+     * data out of its cache to long-term storage.
+     */
+    if conf.read_mode == "catchup" {
+        if conf.catchup_delay > 0 {
+            println!("\t + Waiting {} s before reading the backlog", conf.catchup_delay);
+            thread::sleep(Duration::from_secs(conf.catchup_delay));
+        }
+        println!("Starting Catch-up Read");
+        signal.send(START_CONSTANT).unwrap();
+    }
     drop(out);
 }
 
-fn receiver_handler(signal: mpsc::Receiver<i32>, out: mpsc::Sender<ChannelData>, conf: Config) {
-    let client_factory = create_client(conf.address);
+fn producer_handler(id: usize, shared_producer: SharedProducer, payloads: Payloads, conf: Config, out: mpsc::Sender<ChannelData>, counters: Arc<Counters>) {
+    let message_num   = split_share(conf.message_num, conf.producers, id as u32);
+    let producer_rate = split_share(conf.producer_rate, conf.producers, id as u32);
+    /*
+     * Every message is handed to the pool at its intended send time of the
+     * open-loop schedule, the pool keeps up to producer_rate sends in flight.
+     */
+    let pool         = ThreadPool::new(producer_rate as usize);
+    let mut sizes    = conf.size_sampler(id as u64 + 1);
+    let mut keys     = conf.key_sampler(id as u64 + 1);
+    let mut schedule = RateSchedule::new(producer_rate, &conf.arrival, message_num, conf.duration);
+    let ben_start    = Utc::now();
+    while let Some(intended) = schedule.wait() {
+        let out_cloned     = out.clone();
+        let payload_cloned = payload::sized(&payloads[schedule.sent() as usize % payloads.len()], sizes.sample());
+        let bytes          = payload_cloned.len() as u64;
+        let arc_producer   = Arc::clone(&shared_producer);
+        let counters       = Arc::clone(&counters);
+        let sequence       = counters.next_sequence();
+        let key            = keys.sample();
+        let verify         = conf.verify;
+        pool.execute(move || {
+            let res = write_one_event(arc_producer, payload_cloned, sequence, key, verify);
+            match res {
+                Ok((sent, value)) => {
+                    /*
+                     * Corrected latency counts from the intended send time, so the
+                     * time spent queued in the pool or waiting for the writer lock
+                     * while the system stalls is not hidden from the percentiles.
+                     */
+                    let corrected = if value >= 0.0 {
+                        counters.add_written(bytes);
+                        counters.add_acked(id);
+                        intended.elapsed().as_micros() as f64 / 1000.0
+                    } else {
+                        value
+                    };
+                    out_cloned.send(ChannelData::WriteLatency(id, value, corrected, sent, bytes)).unwrap()
+                },
+                Err(_) => println!("\t + Error at sending")
+            };
+        });
+        if schedule.sent().is_multiple_of(producer_rate) {
+            println!("\t + Producer {} Messages Sent {}", id, schedule.sent());
+        }
+    }
+    if !schedule.sent().is_multiple_of(producer_rate) {
+        println!("\t + Producer {} Messages Sent {}", id, schedule.sent());
+    }
+
+    println!("\t + Producer {} waiting the pool to finish", id);
+    pool.join();
+
+    let ben_ends = Utc::now();
+    let duration = get_difference(ben_start, ben_ends);
+    out.send(ChannelData::ProducerDuration(id, duration)).unwrap();
+}
+
+fn receiver_handler(signal: mpsc::Receiver<i32>, out: mpsc::Sender<ChannelData>, conf: Config, driver: Arc<dyn Driver>, counters: Arc<Counters>) {
     // Pause before everything is working
     loop {
         if let Ok(msg) = signal.try_recv() {
@@ -245,55 +492,167 @@ fn receiver_handler(signal: mpsc::Receiver<i32>, out: mpsc::Sender<ChannelData>,
         }
         thread::sleep(Duration::from_millis(10));
     }
-    // Start Reading Messages
-    let mut i = (conf.message_warmup as i32) * (-1);
-    let scoped_stream = get_scoped_stream(conf.scope, conf.stream);
-    client_factory.runtime().block_on(async {
-        let rg = client_factory.create_reader_group("rg".to_string(), scoped_stream).await;
-        let mut reader = rg.create_reader("r1".to_string()).await;
-        
-        if let Some(mut slice) = reader
-            .acquire_segment()
-            .await
-            .expect("Failed to acquire segment since the reader is offline")
-        {
-            loop {
-                let time1 = Utc::now();
-                let read_event = slice.next();
-                if read_event.is_some() {
-                    i += 1;
-                    let event_len = read_event.unwrap().value.as_slice().len() as u64;
-                    let time2     = Utc::now();
-                    let latency   = get_difference(time1, time2);
-                    
-                    if event_len != conf.message_size {
-                        println!("\t - Error at reading: expected {} got {}", conf.message_size, event_len);
-                        continue;
-                    }
-                    if i > 0 {
-                        out.send(ChannelData::ReadLatency(latency)).unwrap();
-                        if i % (conf.producer_rate as i32) == 0 {
-                            println!("\t - Messages Read {}", i);
-                        }
-                    }
-                } else {
-                    reader.release_segment(slice).await.unwrap();
-                    if let Some(new_slice) = reader
-                        .acquire_segment()
-                        .await
-                        .expect("Failed to acquire segment since the reader is offline") {
-                        slice = new_slice;
-                    } else {
-                        println!("\t - No more data to read");
-                        break;
-                    }
-                }
+    // Start Reading Messages, every reader group gets its own set of readers
+    let verifiers: Vec<Option<Arc<Verifier>>> = (0..conf.subscriptions)
+        .map(|_| conf.verify.then(|| Arc::new(Verifier::new(conf.producers))))
+        .collect();
+    let mut handlers = Vec::new();
+    for (subscription, group_verifier) in verifiers.iter().enumerate() {
+        for reader in 0..conf.consumers as usize {
+            let verifier = group_verifier.clone();
+            let counters = Arc::clone(&counters);
+            let driver   = Arc::clone(&driver);
+            let conf     = conf.clone();
+            let out      = out.clone();
+            handlers.push(thread::spawn(move || {
+                consumer_handler(subscription, reader, verifier, counters, driver, conf, out);
+            }));
+        }
+    }
+
+    // Sample the lag of every reader group until all readers are done
+    let done     = Arc::new(AtomicBool::new(false));
+    let done_cpy = Arc::clone(&done);
+    let out_cpy  = out.clone();
+    let groups   = conf.subscriptions as usize;
+    let counters_cpy = Arc::clone(&counters);
+    let sampler  = thread::spawn(move || {
+        while !done_cpy.load(Ordering::SeqCst) {
+            for subscription in 0..groups {
+                out_cpy.send(ChannelData::SubscriptionLag(subscription, counters.lag(subscription))).unwrap();
             }
+            thread::sleep(LAG_SAMPLE_INTERVAL);
         }
-        reader
-            .reader_offline()
-            .await
-            .expect("failed to mark the reader offline");
     });
+
+    for handler in handlers {
+        if let Err(e) = handler.join() {
+            println!("\t - Consumer thread panicked: {:?}", e);
+        }
+    }
+    done.store(true, Ordering::SeqCst);
+    let _ = sampler.join();
+
+    // Every writer has closed by now, so the acknowledged events are final
+    let acked = counters_cpy.acked();
+    for (subscription, verifier) in verifiers.iter().enumerate() {
+        if let Some(verifier) = verifier {
+            let result = verifier.result(subscription, conf.reader_group_name(subscription), &acked);
+            println!(
+                "\t - {} verified {} events: {} lost, {} duplicated, {} out of order, {} corrupted, {} unverified",
+                result.name, result.events, result.lost, result.duplicates, result.out_of_order, result.corrupted, result.unverified
+            );
+            out.send(ChannelData::Verification(result)).unwrap();
+        }
+    }
     drop(out);
 }
+
+/*
+ * Polls the segments of the stream until the benchmark is done and reports
+ * every change of their number, so scaling events can be lined up with the
+ * latency time-series. Stops at the first driver that cannot tell.
+ */
+fn poll_segments(driver: Arc<dyn Driver>, interval: Duration, running: Arc<AtomicUsize>, out: mpsc::Sender<ChannelData>) {
+    let mut last = None;
+    while running.load(Ordering::SeqCst) > 0 {
+        match driver.segment_count() {
+            Ok(Some(segments)) if last != Some(segments) => {
+                if let Some(last) = last {
+                    println!("\t i Stream scaled from {} to {} segments", last, segments);
+                }
+                out.send(ChannelData::SegmentCount(Utc::now(), segments)).unwrap();
+                last = Some(segments);
+            },
+            Ok(Some(_)) => {},
+            Ok(None) => return,
+            Err(e)   => println!("\t i Error at polling the stream segments: {}", e),
+        }
+        let next_poll = Instant::now() + interval;
+        while running.load(Ordering::SeqCst) > 0 && Instant::now() < next_poll {
+            thread::sleep(POLL_STOP_CHECK.min(next_poll.saturating_duration_since(Instant::now())));
+        }
+    }
+}
+
+fn consumer_handler(subscription: usize, reader: usize, verifier: Option<Arc<Verifier>>, counters: Arc<Counters>, driver: Arc<dyn Driver>, conf: Config, out: mpsc::Sender<ChannelData>) {
+    let id           = subscription * conf.consumers as usize + reader;
+    let group_name   = conf.reader_group_name(subscription);
+    let reader_name  = format!("r{}", reader + 1);
+    let mut consumer = driver.create_consumer(&group_name, &reader_name).expect("create consumer");
+    let ben_start    = Utc::now();
+    let mut ben_ends = ben_start;
+    let mut progress = Instant::now();
+    let mut segments: HashMap<String, u64> = HashMap::new();
+    loop {
+        let time1 = Utc::now();
+        let read_event = match consumer.receive() {
+            Ok(Some(event)) => event,
+            Ok(None) => {
+                println!("\t - Reader {}/{} no more data to read", group_name, reader_name);
+                break;
+            },
+            Err(e) => {
+                println!("\t - Reader {}/{} error at reading: {}", group_name, reader_name, e);
+                break;
+            }
+        };
+        let i         = counters.add_read(subscription);
+        if conf.read_message_num > 0 && i > conf.read_message_num as i64 {
+            break;
+        }
+        let event_len = read_event.len() as u64;
+        let time2     = Utc::now();
+        let latency   = get_difference(time1, time2);
+        if let Some(verifier) = &verifier {
+            verifier.check(reader, &read_event);
+        }
+
+        /*
+         * Events carry their size in the header, fixed sizes are also checked
+         * without one unless the events were written by another tool.
+         */
+        let header   = Header::parse(&read_event);
+        let expected = match (&header, &conf.size_distribution) {
+            (Some(header), _) => Some(header.size as u64),
+            (None, SizeDistribution::Fixed { size }) if conf.mode != "read_only" => Some(*size),
+            (None, _)         => None,
+        };
+        if let Some(expected) = expected.filter(|&expected| expected != event_len) {
+            println!("\t - Error at reading: expected {} got {}", expected, event_len);
+            continue;
+        }
+        if i > 0 {
+            ben_ends = time2;
+            counters.add_read_bytes(event_len);
+            if let Some(segment) = consumer.segment() {
+                *segments.entry(segment).or_insert(0) += 1;
+            }
+            out.send(ChannelData::ReadLatency(id, latency, time2, event_len)).unwrap();
+            if let Some(header) = header {
+                out.send(ChannelData::EndToEndLatency(get_difference(header.time(), time2))).unwrap();
+            }
+            if progress.elapsed() >= READ_PROGRESS_INTERVAL {
+                progress = Instant::now();
+                println!("\t - {} Messages Read {}", group_name, i);
+            }
+        }
+    }
+    out.send(ChannelData::ConsumerDuration(id, get_difference(ben_start, ben_ends))).unwrap();
+    out.send(ChannelData::SegmentEvents(subscription, segments)).unwrap();
+    consumer
+        .close()
+        .expect("failed to mark the reader offline");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_share_hands_out_the_remainder_first() {
+        let shares: Vec<u32> = (0..3).map(|index| split_share(10, 3, index)).collect();
+        assert_eq!(shares, vec![4, 3, 3]);
+        assert_eq!((0..4).map(|index| split_share(2, 4, index)).sum::<u32>(), 2);
+    }
+}
@@ -0,0 +1,151 @@
+use std::thread;
+use std::sync::Arc;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Upper bounds in seconds of the latency histogram buckets, +Inf is implicit.
+const LATENCY_BUCKETS: [f64; 15] = [
+    0.0001, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+struct PromHistogram {
+    buckets: Vec<AtomicU64>,
+    count:   AtomicU64,
+    // Sum of the observations in microseconds
+    sum:     AtomicU64,
+}
+
+/*
+ * Counters and latency histograms of the run, exposed in the Prometheus text
+ * format on `metrics_address` so long runs can be watched while they happen.
+ * They are fed from the result channel drained by main.
+ */
+pub struct Metrics {
+    events_written: AtomicU64,
+    events_read:    AtomicU64,
+    bytes_written:  AtomicU64,
+    bytes_read:     AtomicU64,
+    write_errors:   AtomicU64,
+    write_latency:  PromHistogram,
+    read_latency:   PromHistogram,
+    e2e_latency:    PromHistogram,
+}
+
+impl PromHistogram {
+    fn new() -> PromHistogram {
+        PromHistogram {
+            buckets: LATENCY_BUCKETS.iter().map(|_| AtomicU64::new(0)).collect(),
+            count:   AtomicU64::new(0),
+            sum:     AtomicU64::new(0),
+        }
+    }
+
+    fn observe(&self, millis: f64) {
+        let seconds = millis / 1000.0;
+        if let Some(bucket) = LATENCY_BUCKETS.iter().position(|&bound| seconds <= bound) {
+            self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add((millis * 1000.0).round() as u64, Ordering::Relaxed);
+    }
+
+    // Buckets are stored per range and exposed cumulatively as Prometheus expects.
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        out.push_str(&format!("# HELP {} {}\n# TYPE {} histogram\n", name, help, name));
+        let mut cumulative = 0;
+        for (bound, bucket) in LATENCY_BUCKETS.iter().zip(&self.buckets) {
+            cumulative += bucket.load(Ordering::Relaxed);
+            out.push_str(&format!("{}_bucket{{le=\"{}\"}} {}\n", name, bound, cumulative));
+        }
+        let count = self.count.load(Ordering::Relaxed);
+        out.push_str(&format!("{}_bucket{{le=\"+Inf\"}} {}\n", name, count));
+        out.push_str(&format!("{}_sum {}\n", name, self.sum.load(Ordering::Relaxed) as f64 / 1_000_000.0));
+        out.push_str(&format!("{}_count {}\n", name, count));
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Arc<Metrics> {
+        Arc::new(Metrics {
+            events_written: AtomicU64::new(0),
+            events_read:    AtomicU64::new(0),
+            bytes_written:  AtomicU64::new(0),
+            bytes_read:     AtomicU64::new(0),
+            write_errors:   AtomicU64::new(0),
+            write_latency:  PromHistogram::new(),
+            read_latency:   PromHistogram::new(),
+            e2e_latency:    PromHistogram::new(),
+        })
+    }
+
+    pub fn record_write(&self, latency: f64, bytes: u64) {
+        if latency >= 0.0 {
+            self.events_written.fetch_add(1, Ordering::Relaxed);
+            self.bytes_written.fetch_add(bytes, Ordering::Relaxed);
+            self.write_latency.observe(latency);
+        } else {
+            self.write_errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn record_read(&self, latency: f64, bytes: u64) {
+        self.events_read.fetch_add(1, Ordering::Relaxed);
+        self.bytes_read.fetch_add(bytes, Ordering::Relaxed);
+        self.read_latency.observe(latency);
+    }
+
+    pub fn record_e2e(&self, latency: f64) {
+        self.e2e_latency.observe(latency);
+    }
+
+    // Serves /metrics on a background thread for the rest of the process lifetime.
+    pub fn serve(self: &Arc<Self>, address: &str) -> std::io::Result<()> {
+        let listener = TcpListener::bind(address)?;
+        println!("\t i Serving metrics on http://{}/metrics", address);
+        let metrics = Arc::clone(self);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if let Err(e) = metrics.respond(stream) {
+                    println!("\t i Error at serving metrics: {}", e);
+                }
+            }
+        });
+        Ok(())
+    }
+
+    fn respond(&self, mut stream: TcpStream) -> std::io::Result<()> {
+        let mut request_line = String::new();
+        BufReader::new(&stream).read_line(&mut request_line)?;
+        let (status, body) = if request_line.starts_with("GET /metrics") {
+            ("200 OK", self.render())
+        } else {
+            ("404 Not Found", String::new())
+        };
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status, body.len(), body
+        );
+        stream.write_all(response.as_bytes())
+    }
+
+    fn render(&self) -> String {
+        let written = self.events_written.load(Ordering::Relaxed);
+        let read    = self.events_read.load(Ordering::Relaxed);
+        let mut out = String::new();
+        let counters = [
+            ("benchmark_events_written_total", "Events acknowledged to the writers.", written),
+            ("benchmark_events_read_total", "Events read by all readers.", read),
+            ("benchmark_bytes_written_total", "Payload bytes acknowledged to the writers.", self.bytes_written.load(Ordering::Relaxed)),
+            ("benchmark_bytes_read_total", "Payload bytes read by all readers.", self.bytes_read.load(Ordering::Relaxed)),
+            ("benchmark_write_errors_total", "Failed writes.", self.write_errors.load(Ordering::Relaxed)),
+        ];
+        for (name, help, value) in counters {
+            out.push_str(&format!("# HELP {} {}\n# TYPE {} counter\n{} {}\n", name, help, name, name, value));
+        }
+        self.write_latency.render(&mut out, "benchmark_write_latency_seconds", "Write latency.");
+        self.read_latency.render(&mut out, "benchmark_read_latency_seconds", "Read latency.");
+        self.e2e_latency.render(&mut out, "benchmark_e2e_latency_seconds", "End-to-end latency from send to receive.");
+        out
+    }
+}
@@ -0,0 +1,135 @@
+use std::fs;
+use std::sync::Arc;
+use rand::Rng;
+use rand::RngCore;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use crate::config::Config;
+
+// Text repeated by the `text` payload type.
+const TEXT: &str = "The quick brown fox jumps over the lazy dog while the stream keeps on scaling. ";
+// Block size used to interleave compressible and random bytes.
+const COMPRESSIBLE_BLOCK: usize = 64;
+
+pub type Payloads = Arc<Vec<Vec<u8>>>;
+
+/*
+ * Builds every payload of the run before the benchmark starts, so generating
+ * them does not skew the latencies. Producers rotate through them round-robin.
+ * All generated types use `payload_seed`, so two runs send the same bytes.
+ */
+pub fn generate(conf: &Config) -> std::io::Result<Payloads> {
+    let mut rng  = StdRng::seed_from_u64(conf.payload_seed);
+    let size     = conf.payload_size as usize;
+    let count    = conf.payload_count as usize;
+    let payloads = match conf.payload_type.as_str() {
+        "random"       => (0..count).map(|_| random(&mut rng, size)).collect(),
+        "compressible" => (0..count).map(|_| compressible(&mut rng, size, conf.payload_compress_ratio)).collect(),
+        "text"         => (0..count).map(|_| text(rng.gen_range(0..TEXT.len()), size)).collect(),
+        "corpus"       => corpus(&conf.payload_dir, size)?,
+        _              => vec![conf.get_payload()],
+    };
+    Ok(Arc::new(payloads))
+}
+
+// Payload of an event of the given size, repeating or cutting the generated one.
+pub fn sized(payload: &[u8], size: usize) -> Vec<u8> {
+    if size == payload.len() || payload.is_empty() {
+        return payload.to_vec();
+    }
+    payload.iter().copied().cycle().take(size).collect()
+}
+
+fn random(rng: &mut StdRng, size: usize) -> Vec<u8> {
+    let mut payload = vec![0u8; size];
+    rng.fill_bytes(&mut payload);
+    payload
+}
+
+// Every block starts with `compressibility` of its bytes set to zero, the rest is random.
+fn compressible(rng: &mut StdRng, size: usize, compressibility: f64) -> Vec<u8> {
+    let mut payload = random(rng, size);
+    let zeros = (COMPRESSIBLE_BLOCK as f64 * compressibility).round() as usize;
+    for block in payload.chunks_mut(COMPRESSIBLE_BLOCK) {
+        let zeros = zeros.min(block.len());
+        block[..zeros].fill(0);
+    }
+    payload
+}
+
+fn text(offset: usize, size: usize) -> Vec<u8> {
+    TEXT.bytes().cycle().skip(offset).take(size).collect()
+}
+
+// One payload per file of the directory in name order, repeated or cut to `size` bytes.
+fn corpus(dir: &str, size: usize) -> std::io::Result<Vec<Vec<u8>>> {
+    let mut paths: Vec<_> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .collect();
+    paths.sort();
+    let mut payloads = Vec::new();
+    for path in paths {
+        let content = fs::read(&path)?;
+        if !content.is_empty() {
+            payloads.push(content.iter().copied().cycle().take(size).collect());
+        }
+    }
+    if payloads.is_empty() {
+        return Err(std::io::Error::new(std::io::ErrorKind::NotFound, format!("No corpus files in {}", dir)));
+    }
+    Ok(payloads)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_conf(payload_type: &str, seed: u64) -> Config {
+        let mut conf = Config::new();
+        conf.payload_type  = payload_type.to_string();
+        conf.payload_seed  = seed;
+        conf.payload_count = 3;
+        conf.payload_size  = 1000;
+        conf
+    }
+
+    #[test]
+    fn the_seed_makes_payloads_reproducible() {
+        let first  = generate(&test_conf("random", 7)).unwrap();
+        let second = generate(&test_conf("random", 7)).unwrap();
+        let other  = generate(&test_conf("random", 8)).unwrap();
+        assert_eq!(first, second);
+        assert_ne!(first, other);
+        assert_eq!(first.len(), 3);
+        assert!(first.iter().all(|payload| payload.len() == 1000));
+        assert_ne!(first[0], first[1]);
+    }
+
+    #[test]
+    fn compressible_payloads_zero_the_configured_share() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let payload = compressible(&mut rng, 6400, 0.25);
+        for block in payload.chunks(COMPRESSIBLE_BLOCK) {
+            assert!(block[..16].iter().all(|&byte| byte == 0));
+        }
+        // The random rest of the blocks is almost never zero
+        let zeros = payload.iter().filter(|&&byte| byte == 0).count();
+        assert!((1600..1800).contains(&zeros), "zeros {}", zeros);
+    }
+
+    #[test]
+    fn text_payloads_repeat_the_text() {
+        let payload = text(4, TEXT.len() + 5);
+        assert!(payload.starts_with(b"quick brown fox"));
+        assert_eq!(payload[TEXT.len() - 4..TEXT.len() + 1], *b"The q");
+    }
+
+    #[test]
+    fn sized_payloads_repeat_or_cut_the_generated_one() {
+        assert_eq!(sized(&[1, 2, 3], 7), vec![1, 2, 3, 1, 2, 3, 1]);
+        assert_eq!(sized(&[1, 2, 3], 2), vec![1, 2]);
+        assert_eq!(sized(&[1, 2, 3], 3), vec![1, 2, 3]);
+        assert!(sized(&[], 5).is_empty());
+    }
+}
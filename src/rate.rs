@@ -0,0 +1,135 @@
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+use rand::Rng;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+
+// A schedule either sends a number of messages or runs until a wall-clock deadline.
+enum RunEnd {
+    Messages(u32),
+    Deadline(Instant),
+}
+
+/*
+ * Open-loop send schedule. The intended send time of every message is derived
+ * from the schedule start, not from when the previous send finished, so a slow
+ * send does not push back the following ones and the rate does not drift.
+ * `uniform` spaces messages evenly, `poisson` draws exponential gaps with the
+ * same mean.
+ */
+pub struct RateSchedule {
+    start:    Instant,
+    offset:   f64,
+    interval: f64,
+    poisson:  bool,
+    rng:      StdRng,
+    end:      RunEnd,
+    sent:     u32,
+}
+
+impl RateSchedule {
+    // A duration of 0 seconds sends `messages` messages, otherwise it runs for `duration` seconds.
+    pub fn new(rate: u32, arrival: &str, messages: u32, duration: u64) -> RateSchedule {
+        let start = Instant::now();
+        RateSchedule {
+            start,
+            offset:   0.0,
+            interval: 1.0 / rate as f64,
+            poisson:  arrival == "poisson",
+            rng:      StdRng::from_entropy(),
+            end:      if duration > 0 {
+                RunEnd::Deadline(start + Duration::from_secs(duration))
+            } else {
+                RunEnd::Messages(messages)
+            },
+            sent:     0,
+        }
+    }
+
+    // Messages handed out so far.
+    pub fn sent(&self) -> u32 {
+        self.sent
+    }
+
+    // Intended send time of the next message.
+    fn next_send(&mut self) -> Instant {
+        let send_time = self.start + Duration::from_secs_f64(self.offset);
+        let gap = if self.poisson {
+            -(1.0 - self.rng.gen::<f64>()).ln() * self.interval
+        } else {
+            self.interval
+        };
+        self.offset += gap;
+        send_time
+    }
+
+    /*
+     * Sleeps until the intended send time of the next message and returns it,
+     * immediately when behind schedule. Returns None once the schedule is over.
+     */
+    pub fn wait(&mut self) -> Option<Instant> {
+        if let RunEnd::Messages(messages) = self.end {
+            if self.sent >= messages {
+                return None;
+            }
+        }
+        let send_time = self.next_send();
+        if let RunEnd::Deadline(deadline) = self.end {
+            if send_time >= deadline {
+                return None;
+            }
+        }
+        let now = Instant::now();
+        if send_time > now {
+            thread::sleep(send_time - now);
+        }
+        self.sent += 1;
+        Some(send_time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uniform_schedule_spaces_messages_evenly() {
+        let mut schedule = RateSchedule::new(1000, "uniform", 20, 0);
+        let start = schedule.start;
+        let mut send_times = Vec::new();
+        while let Some(send_time) = schedule.wait() {
+            send_times.push(send_time);
+        }
+        assert_eq!(schedule.sent(), 20);
+        for (i, send_time) in send_times.iter().enumerate() {
+            let offset = send_time.duration_since(start).as_secs_f64();
+            assert!((offset - i as f64 / 1000.0).abs() < 1e-6);
+        }
+        // The last message is not sent before its intended time
+        assert!(start.elapsed() >= Duration::from_millis(19));
+    }
+
+    #[test]
+    fn poisson_schedule_keeps_the_mean_rate() {
+        let mut schedule = RateSchedule::new(1_000_000, "poisson", 10_000, 0);
+        let start = schedule.start;
+        let mut last = start;
+        while let Some(send_time) = schedule.wait() {
+            assert!(send_time >= last);
+            last = send_time;
+        }
+        // 10000 gaps of 1 us on average
+        let span = last.duration_since(start).as_secs_f64();
+        assert!((0.008..0.012).contains(&span), "span {}", span);
+    }
+
+    #[test]
+    fn duration_schedule_ends_at_the_deadline() {
+        let mut schedule = RateSchedule::new(100, "uniform", 5, 1);
+        while schedule.wait().is_some() {}
+        // The message count is ignored, no message is scheduled at or past the deadline
+        assert!((99..=101).contains(&schedule.sent()), "sent {}", schedule.sent());
+        assert!(schedule.start.elapsed() < Duration::from_millis(1100));
+    }
+}
@@ -0,0 +1,190 @@
+use std::thread;
+use std::fs::File;
+use std::io::Write;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+use std::thread::JoinHandle;
+use std::sync::atomic::{AtomicBool, Ordering};
+use chrono::prelude::*;
+use serde::Serialize;
+use crate::config::Config;
+use crate::counters::Counters;
+use crate::histogram::LatencyHistogram;
+
+// How often the reporter thread checks whether the run is over.
+const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Serialize)]
+pub struct ProgressReport {
+    pub timestamp:           String,
+    pub publish_rate:        f64,
+    pub publish_throughput:  f64,
+    pub publish_errors:      f64,
+    pub consume_rate:        f64,
+    pub consume_throughput:  f64,
+    pub backlog:             i64,
+    pub write_latency_avg:   f64,
+    pub write_latency_50pct: f64,
+    pub write_latency_99pct: f64,
+    pub write_latency_max:   f64,
+    pub e2e_latency_avg:     f64,
+    pub e2e_latency_50pct:   f64,
+    pub e2e_latency_99pct:   f64,
+    pub e2e_latency_max:     f64,
+}
+
+struct IntervalStats {
+    write_errors:    u64,
+    write_latencies: LatencyHistogram,
+    e2e_latencies:   LatencyHistogram,
+}
+
+/*
+ * Periodic progress lines in the style of the OpenMessaging Benchmark. Rates
+ * and backlog come from the shared counters, latencies of the last interval
+ * are fed by the thread draining the result channel.
+ */
+pub struct Reporter {
+    interval:    Duration,
+    report_file: String,
+    counters:    Arc<Counters>,
+    stats:       Mutex<IntervalStats>,
+    done:        AtomicBool,
+}
+
+impl IntervalStats {
+    fn new() -> IntervalStats {
+        IntervalStats {
+            write_errors:    0,
+            write_latencies: LatencyHistogram::compact(),
+            e2e_latencies:   LatencyHistogram::compact(),
+        }
+    }
+}
+
+impl Reporter {
+    pub fn new(conf: &Config, counters: Arc<Counters>) -> Arc<Reporter> {
+        Arc::new(Reporter {
+            interval:    Duration::from_secs(conf.report_interval),
+            report_file: conf.report_file.clone(),
+            counters,
+            stats:       Mutex::new(IntervalStats::new()),
+            done:        AtomicBool::new(false),
+        })
+    }
+
+    fn lock_stats(&self) -> std::sync::MutexGuard<'_, IntervalStats> {
+        self.stats.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn record_write(&self, latency: f64) {
+        let mut stats = self.lock_stats();
+        if latency >= 0.0 {
+            stats.write_latencies.record(latency);
+        } else {
+            stats.write_errors += 1;
+        }
+    }
+
+    pub fn record_e2e(&self, latency: f64) {
+        self.lock_stats().e2e_latencies.record(latency);
+    }
+
+    // Reports every report_interval seconds until stop is called, a 0 interval disables it.
+    pub fn start(self: &Arc<Self>) -> Option<JoinHandle<()>> {
+        if self.interval.is_zero() {
+            return None;
+        }
+        let reporter = Arc::clone(self);
+        Some(thread::spawn(move || reporter.run()))
+    }
+
+    pub fn stop(&self) {
+        self.done.store(true, Ordering::SeqCst);
+    }
+
+    fn run(&self) {
+        let mut file = if self.report_file.is_empty() {
+            None
+        } else {
+            match File::create(&self.report_file) {
+                Ok(file) => Some(file),
+                Err(e)   => {
+                    println!("\t i Error at creating report file {}: {}", self.report_file, e);
+                    None
+                }
+            }
+        };
+        let mut last_time    = Instant::now();
+        let mut last_written = self.counters.total_written();
+        let mut last_read    = self.counters.total_read();
+        let mut last_bytes   = (self.counters.total_written_bytes(), self.counters.total_read_bytes());
+        while !self.done.load(Ordering::SeqCst) {
+            thread::sleep(STOP_CHECK_INTERVAL);
+            if last_time.elapsed() < self.interval {
+                continue;
+            }
+            let elapsed  = last_time.elapsed().as_secs_f64();
+            let written  = self.counters.total_written();
+            let read     = self.counters.total_read();
+            let bytes    = (self.counters.total_written_bytes(), self.counters.total_read_bytes());
+            let stats    = std::mem::replace(&mut *self.lock_stats(), IntervalStats::new());
+            let report   = self.report(
+                elapsed,
+                (written - last_written, bytes.0 - last_bytes.0),
+                (read - last_read, bytes.1 - last_bytes.1),
+                &stats
+            );
+            last_time    = Instant::now();
+            last_written = written;
+            last_read    = read;
+            last_bytes   = bytes;
+
+            println!(
+                "Pub rate {:.1} msg/s / {:.1} MB/s | Pub err {:.1} err/s | Cons rate {:.1} msg/s / {:.1} MB/s | Backlog: {:.1} K | Pub Latency (ms) avg: {:.3} - 50%: {:.3} - 99%: {:.3} - Max: {:.3} | E2E Latency (ms) avg: {:.3} - 50%: {:.3} - 99%: {:.3} - Max: {:.3}",
+                report.publish_rate, report.publish_throughput, report.publish_errors,
+                report.consume_rate, report.consume_throughput, report.backlog as f64 / 1000.0,
+                report.write_latency_avg, report.write_latency_50pct, report.write_latency_99pct, report.write_latency_max,
+                report.e2e_latency_avg, report.e2e_latency_50pct, report.e2e_latency_99pct, report.e2e_latency_max
+            );
+            if let Some(file) = file.as_mut() {
+                let _ = writeln!(file, "{}", serde_json::to_string(&report).unwrap());
+            }
+        }
+    }
+
+    // Events and bytes written and read during the interval.
+    fn report(&self, elapsed: f64, written: (i64, u64), read: (i64, u64), stats: &IntervalStats) -> ProgressReport {
+        let publish_rate = written.0 as f64 / elapsed;
+        let consume_rate = read.0 as f64 / elapsed;
+        let (write_avg, write_50, write_99, write_max) = Self::latencies(&stats.write_latencies);
+        let (e2e_avg, e2e_50, e2e_99, e2e_max)         = Self::latencies(&stats.e2e_latencies);
+        ProgressReport {
+            timestamp:           Utc::now().to_rfc3339(),
+            publish_rate,
+            publish_throughput:  written.1 as f64 / elapsed / 1_000_000.0,
+            publish_errors:      stats.write_errors as f64 / elapsed,
+            consume_rate,
+            consume_throughput:  read.1 as f64 / elapsed / 1_000_000.0,
+            backlog:             self.counters.total_lag(),
+            write_latency_avg:   write_avg,
+            write_latency_50pct: write_50,
+            write_latency_99pct: write_99,
+            write_latency_max:   write_max,
+            e2e_latency_avg:     e2e_avg,
+            e2e_latency_50pct:   e2e_50,
+            e2e_latency_99pct:   e2e_99,
+            e2e_latency_max:     e2e_max,
+        }
+    }
+
+    // Average, 50%, 99% and max, all zero when nothing was recorded in the interval.
+    fn latencies(histogram: &LatencyHistogram) -> (f64, f64, f64, f64) {
+        if histogram.is_empty() {
+            return (0.0, 0.0, 0.0, 0.0);
+        }
+        (histogram.mean(), histogram.percentile(50.0), histogram.percentile(99.0), histogram.max())
+    }
+}
@@ -1,24 +1,142 @@
 use std::fs::File;
 use std::io::Write;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
 use chrono::prelude::*;
 use serde::Serialize;
 use serde::Deserialize;
 use crate::config::Config;
-use statrs::statistics::Max;
-use statrs::statistics::Data;
-use std::collections::HashMap;
-use statrs::statistics::Distribution;
-use statrs::statistics::OrderStatistics;
+use crate::histogram::PercentileRow;
+use crate::verify::VerificationResult;
+use crate::retention::RetentionResult;
+use crate::histogram::LatencyHistogram;
+use crate::histogram::SizeHistogram;
+
+// Milliseconds after the end of an interval during which late writes and reads still count in its percentiles.
+const TIMESERIES_GRACE: u64 = 5000;
+
+#[derive(Serialize, Deserialize)]
+pub struct ProducerResult {
+    pub id:                  usize,
+    pub message_num:         u32,
+    pub duration:            f64,
+    pub write_latency_50pct: f64,
+    pub write_latency_75pct: f64,
+    pub write_latency_95pct: f64,
+    pub write_latency_99pct: f64,
+    pub write_latency_avg:   f64,
+    pub write_latency_max:   f64,
+    pub throughput:          f64,
+    pub bytes:               u64,
+    #[serde(skip)]
+    pub write_latencies:     LatencyHistogram,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ConsumerResult {
+    pub id:                 usize,
+    pub subscription:       usize,
+    pub message_num:        u32,
+    pub duration:           f64,
+    pub read_latency_50pct: f64,
+    pub read_latency_75pct: f64,
+    pub read_latency_95pct: f64,
+    pub read_latency_99pct: f64,
+    pub read_latency_avg:   f64,
+    pub read_latency_max:   f64,
+    pub throughput:         f64,
+    pub bytes:              u64,
+    #[serde(skip)]
+    pub read_latencies:     LatencyHistogram,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SubscriptionResult {
+    pub id:          usize,
+    pub name:        String,
+    pub message_num: u32,
+    pub duration:    f64,
+    pub throughput:  f64,
+    pub bytes:       u64,
+    pub lag_avg:     f64,
+    pub lag_max:     i64,
+    pub segments:    BTreeMap<String, u64>,
+    #[serde(skip_serializing)]
+    pub lags:        Vec<i64>,
+}
+
+// Scaling policy the benchmark stream was created with.
+#[derive(Serialize, Deserialize)]
+pub struct ScalingPolicy {
+    pub scale_type:       String,
+    pub target_rate:      i32,
+    pub scale_factor:     i32,
+    pub min_num_segments: i32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SegmentCountSample {
+    // Seconds since the benchmark start, on the same axis as the time-series
+    pub time:      f64,
+    pub timestamp: String,
+    pub segments:  usize,
+}
+
+// Draining the backlog of a catch-up read, the run is caught up once every reader group is.
+#[derive(Serialize, Deserialize)]
+pub struct CatchupResult {
+    pub delay:            u64,
+    pub backlog_events:   u32,
+    pub backlog_bytes:    u64,
+    pub time_to_catch_up: f64,
+    pub drain_rate:       f64,
+    pub drain_throughput: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RateSample {
+    pub second:   usize,
+    pub target:   u32,
+    pub achieved: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TimeSeriesBucket {
+    pub start:               f64,
+    pub messages_sent:       u64,
+    pub messages_read:       u64,
+    pub bytes_sent:          u64,
+    pub bytes_read:          u64,
+    pub write_latency_50pct: f64,
+    pub write_latency_95pct: f64,
+    pub write_latency_99pct: f64,
+    pub read_latency_50pct:  f64,
+    pub read_latency_95pct:  f64,
+    pub read_latency_99pct:  f64,
+    // Dropped once the percentiles of the interval are computed
+    #[serde(skip)]
+    pub write_latencies:     Option<LatencyHistogram>,
+    #[serde(skip)]
+    pub read_latencies:      Option<LatencyHistogram>,
+}
 
 #[derive(Serialize, Deserialize)]
 pub struct TestResult {
     // Test Configuration
-    pub name:         String,
-    pub message_num:  u32,
-    pub message_size: u64,
-    pub scope:        String,
-    pub stream:       String,
-    pub duration:     f64,
+    pub name:          String,
+    pub mode:          String,
+    pub message_num:   u32,
+    pub message_size:  u64,
+    pub message_size_avg:   f64,
+    pub message_size_50pct: u64,
+    pub message_size_95pct: u64,
+    pub message_size_99pct: u64,
+    pub message_size_max:   u64,
+    pub producer_rate: u32,
+    pub scope:         String,
+    pub stream:        String,
+    pub scaling:       ScalingPolicy,
+    pub duration:      f64,
     // Metrics
     pub write_latency_50pct: f64,
     pub write_latency_75pct: f64,
@@ -26,24 +144,232 @@ pub struct TestResult {
     pub write_latency_99pct: f64,
     pub write_latency_avg:   f64,
     pub write_latency_max:   f64,
-    pub write_latency_hist:  HashMap<u32, u32>,
-    pub read_latency_hist:   HashMap<u32, u32>,
+    pub write_latency_hist:  String,
+    pub write_latency_distribution: Vec<PercentileRow>,
+    pub corrected_write_latency_50pct: f64,
+    pub corrected_write_latency_75pct: f64,
+    pub corrected_write_latency_95pct: f64,
+    pub corrected_write_latency_99pct: f64,
+    pub corrected_write_latency_avg:   f64,
+    pub corrected_write_latency_max:   f64,
+    pub corrected_write_latency_hist:  String,
+    pub corrected_write_latency_distribution: Vec<PercentileRow>,
+    pub read_latency_hist:   String,
+    pub read_latency_distribution: Vec<PercentileRow>,
+    pub e2e_latency_50pct:   f64,
+    pub e2e_latency_75pct:   f64,
+    pub e2e_latency_95pct:   f64,
+    pub e2e_latency_99pct:   f64,
+    pub e2e_latency_avg:     f64,
+    pub e2e_latency_max:     f64,
+    pub e2e_latency_hist:    String,
+    pub e2e_latency_distribution: Vec<PercentileRow>,
     pub throughput:          f64,
     pub sent_data:           f64,
-    #[serde(skip_serializing)]
-    pub write_latencies:     Vec<f64>,
-    #[serde(skip_serializing)]
-    pub read_latencies:      Vec<f64>
+    pub sent_bytes:          u64,
+    pub achieved_rate:       f64,
+    pub producers:           Vec<ProducerResult>,
+    pub consumers:           Vec<ConsumerResult>,
+    pub subscriptions:       Vec<SubscriptionResult>,
+    pub rate_series:         Vec<RateSample>,
+    pub timeseries:          Vec<TimeSeriesBucket>,
+    pub segment_timeline:    Vec<SegmentCountSample>,
+    pub verification:        Vec<VerificationResult>,
+    pub retention:           Option<RetentionResult>,
+    pub catchup:             Option<CatchupResult>,
+    #[serde(skip)]
+    pub read_mode:           String,
+    #[serde(skip)]
+    pub catchup_delay:       u64,
+    #[serde(skip)]
+    pub timeseries_interval: u64,
+    #[serde(skip)]
+    pub timeseries_csv:      bool,
+    // Intervals before this one have their percentiles computed
+    #[serde(skip)]
+    pub timeseries_closed:   usize,
+    #[serde(skip)]
+    pub start_time:          DateTime<Utc>,
+    #[serde(skip)]
+    pub write_latencies:     LatencyHistogram,
+    #[serde(skip)]
+    pub corrected_write_latencies: LatencyHistogram,
+    #[serde(skip)]
+    pub read_latencies:      LatencyHistogram,
+    #[serde(skip)]
+    pub e2e_latencies:       LatencyHistogram,
+    #[serde(skip)]
+    pub message_sizes:       SizeHistogram
+}
+
+impl ProducerResult {
+    fn new(id: usize) -> ProducerResult {
+        ProducerResult {
+            id,
+            message_num:         0,
+            duration:            0.0,
+            write_latency_50pct: 0.0,
+            write_latency_75pct: 0.0,
+            write_latency_95pct: 0.0,
+            write_latency_99pct: 0.0,
+            write_latency_avg:   0.0,
+            write_latency_max:   0.0,
+            throughput:          0.0,
+            bytes:               0,
+            write_latencies:     LatencyHistogram::default(),
+        }
+    }
+
+    fn calculate_metrics(&mut self) {
+        self.message_num = self.write_latencies.len() as u32;
+        if self.write_latencies.is_empty() {
+            return;
+        }
+        let data = &self.write_latencies;
+        self.write_latency_50pct = TestResult::round3(data.percentile(50.0));
+        self.write_latency_75pct = TestResult::round3(data.percentile(75.0));
+        self.write_latency_95pct = TestResult::round3(data.percentile(95.0));
+        self.write_latency_99pct = TestResult::round3(data.percentile(99.0));
+        self.write_latency_avg   = TestResult::round3(data.mean());
+        self.write_latency_max   = TestResult::round3(data.max());
+        self.duration  /= 1000.0;
+        let sent_data   = TestResult::calculate_data_sent(self.bytes);
+        self.throughput = sent_data / self.duration;
+    }
+}
+
+impl ConsumerResult {
+    fn new(id: usize, subscription: usize) -> ConsumerResult {
+        ConsumerResult {
+            id,
+            subscription,
+            message_num:        0,
+            duration:           0.0,
+            read_latency_50pct: 0.0,
+            read_latency_75pct: 0.0,
+            read_latency_95pct: 0.0,
+            read_latency_99pct: 0.0,
+            read_latency_avg:   0.0,
+            read_latency_max:   0.0,
+            throughput:         0.0,
+            bytes:              0,
+            read_latencies:     LatencyHistogram::default(),
+        }
+    }
+
+    fn calculate_metrics(&mut self) {
+        self.message_num = self.read_latencies.len() as u32;
+        if self.read_latencies.is_empty() {
+            return;
+        }
+        let data = &self.read_latencies;
+        self.read_latency_50pct = TestResult::round3(data.percentile(50.0));
+        self.read_latency_75pct = TestResult::round3(data.percentile(75.0));
+        self.read_latency_95pct = TestResult::round3(data.percentile(95.0));
+        self.read_latency_99pct = TestResult::round3(data.percentile(99.0));
+        self.read_latency_avg   = TestResult::round3(data.mean());
+        self.read_latency_max   = TestResult::round3(data.max());
+        self.duration  /= 1000.0;
+        let read_data   = TestResult::calculate_data_sent(self.bytes);
+        self.throughput = read_data / self.duration;
+    }
+}
+
+impl SubscriptionResult {
+    fn new(id: usize, name: String) -> SubscriptionResult {
+        SubscriptionResult {
+            id,
+            name,
+            message_num: 0,
+            duration:    0.0,
+            throughput:  0.0,
+            bytes:       0,
+            lag_avg:     0.0,
+            lag_max:     0,
+            segments:    BTreeMap::new(),
+            lags:        Vec::new(),
+        }
+    }
+
+    // Aggregates the readers of this group, it lasts as long as its slowest reader.
+    fn calculate_metrics(&mut self, consumers: &[ConsumerResult]) {
+        for consumer in consumers.iter().filter(|consumer| consumer.subscription == self.id) {
+            self.message_num += consumer.message_num;
+            self.bytes       += consumer.bytes;
+            self.duration     = self.duration.max(consumer.duration);
+        }
+        if self.duration > 0.0 {
+            let read_data   = TestResult::calculate_data_sent(self.bytes);
+            self.throughput = read_data / self.duration;
+        }
+        if !self.lags.is_empty() {
+            self.lag_avg = TestResult::round3(self.lags.iter().sum::<i64>() as f64 / self.lags.len() as f64);
+            self.lag_max = *self.lags.iter().max().unwrap();
+        }
+    }
+}
+
+impl TimeSeriesBucket {
+    fn new(start: f64) -> TimeSeriesBucket {
+        TimeSeriesBucket {
+            start,
+            messages_sent:       0,
+            messages_read:       0,
+            bytes_sent:          0,
+            bytes_read:          0,
+            write_latency_50pct: 0.0,
+            write_latency_95pct: 0.0,
+            write_latency_99pct: 0.0,
+            read_latency_50pct:  0.0,
+            read_latency_95pct:  0.0,
+            read_latency_99pct:  0.0,
+            write_latencies:     Some(LatencyHistogram::compact()),
+            read_latencies:      Some(LatencyHistogram::compact()),
+        }
+    }
+
+    // Computes the percentiles of the interval and frees its histograms, later calls do nothing.
+    fn calculate_metrics(&mut self) {
+        if let Some(latencies) = self.write_latencies.take().filter(|latencies| !latencies.is_empty()) {
+            self.write_latency_50pct = TestResult::round3(latencies.percentile(50.0));
+            self.write_latency_95pct = TestResult::round3(latencies.percentile(95.0));
+            self.write_latency_99pct = TestResult::round3(latencies.percentile(99.0));
+        }
+        if let Some(latencies) = self.read_latencies.take().filter(|latencies| !latencies.is_empty()) {
+            self.read_latency_50pct = TestResult::round3(latencies.percentile(50.0));
+            self.read_latency_95pct = TestResult::round3(latencies.percentile(95.0));
+            self.read_latency_99pct = TestResult::round3(latencies.percentile(99.0));
+        }
+    }
 }
 
 impl TestResult {
     pub fn new(conf: Config) -> TestResult {
+        // Only the side of the run its mode benchmarks gets results
+        let producers = if conf.mode == "read_only" { 0 } else { conf.producers as usize };
+        let groups    = if conf.mode == "write_only" { 0 } else { conf.subscriptions as usize };
+        let subscriptions = (0..groups)
+            .map(|id| SubscriptionResult::new(id, conf.reader_group_name(id)))
+            .collect();
         TestResult { 
             name:                conf.name,
+            mode:                conf.mode,
             message_num:         conf.message_num,
             message_size:        conf.message_size,
+            message_size_avg:    0.0,
+            message_size_50pct:  0,
+            message_size_95pct:  0,
+            message_size_99pct:  0,
+            message_size_max:    0,
+            producer_rate:       conf.producer_rate,
             scope:               conf.scope,
             stream:              conf.stream,
+            scaling:             ScalingPolicy {
+                scale_type:       conf.scale_type,
+                target_rate:      conf.scale_target_rate,
+                scale_factor:     conf.scale_factor,
+                min_num_segments: conf.scale_min_num_segments,
+            },
             duration:            0.0,
             write_latency_50pct: 0.0,
             write_latency_75pct: 0.0,
@@ -51,35 +377,126 @@ impl TestResult {
             write_latency_99pct: 0.0,
             write_latency_avg:   0.0,
             write_latency_max:   0.0,
-            write_latencies:     Vec::new(),
-            read_latencies:      Vec::new(),
-            write_latency_hist:  HashMap::new(),
-            read_latency_hist:   HashMap::new(),
+            corrected_write_latency_50pct: 0.0,
+            corrected_write_latency_75pct: 0.0,
+            corrected_write_latency_95pct: 0.0,
+            corrected_write_latency_99pct: 0.0,
+            corrected_write_latency_avg:   0.0,
+            corrected_write_latency_max:   0.0,
+            e2e_latency_50pct:   0.0,
+            e2e_latency_75pct:   0.0,
+            e2e_latency_95pct:   0.0,
+            e2e_latency_99pct:   0.0,
+            e2e_latency_avg:     0.0,
+            e2e_latency_max:     0.0,
+            write_latencies:     LatencyHistogram::default(),
+            corrected_write_latencies: LatencyHistogram::default(),
+            read_latencies:      LatencyHistogram::default(),
+            e2e_latencies:       LatencyHistogram::default(),
+            message_sizes:       SizeHistogram::default(),
+            write_latency_hist:  String::new(),
+            corrected_write_latency_hist: String::new(),
+            read_latency_hist:   String::new(),
+            e2e_latency_hist:    String::new(),
+            write_latency_distribution:           Vec::new(),
+            corrected_write_latency_distribution: Vec::new(),
+            read_latency_distribution:            Vec::new(),
+            e2e_latency_distribution:             Vec::new(),
             throughput:          0.0,
-            sent_data:           0.0
+            sent_data:           0.0,
+            sent_bytes:          0,
+            achieved_rate:       0.0,
+            producers:           (0..producers).map(ProducerResult::new).collect(),
+            consumers:           (0..groups * conf.consumers as usize)
+                .map(|id| ConsumerResult::new(id, id / conf.consumers as usize))
+                .collect(),
+            subscriptions,
+            rate_series:         Vec::new(),
+            timeseries:          Vec::new(),
+            segment_timeline:    Vec::new(),
+            verification:        Vec::new(),
+            retention:           None,
+            catchup:             None,
+            read_mode:           conf.read_mode,
+            catchup_delay:       conf.catchup_delay,
+            timeseries_interval: conf.timeseries_interval,
+            timeseries_csv:      conf.timeseries_csv,
+            timeseries_closed:   0,
+            start_time:          Utc::now(),
         }
     }
 
     fn round3(value: f64) -> f64 {
-        let rounded_value = (value * 1000.0).round() / 1000.0;
-        rounded_value
+        (value * 1000.0).round() / 1000.0
     }
 
-    fn calculate_data_sent(messages: u128, size: u128)  -> f64 {
-        let value  = messages.checked_mul(size).unwrap_or_else(|| 0);
-        let result = (value / 1000000) as f64;
-        result
+    fn calculate_data_sent(bytes: u64) -> f64 {
+        bytes as f64 / 1000000.0
     }
 
-    pub fn add_write_latency(&mut self, value: f64) {
-        if value > 0.0 {
-            self.write_latencies.push(value);
+    pub fn set_start_time(&mut self, value: DateTime<Utc>) {
+        self.start_time = value;
+    }
+
+    // Successful writes are also counted in the second of the run they were sent in.
+    pub fn add_write_latency(&mut self, producer: usize, value: f64, corrected: f64, sent: DateTime<Utc>, bytes: u64) {
+        if value >= 0.0 {
+            self.write_latencies.record(value);
+            self.message_sizes.record(bytes);
+            self.sent_bytes += bytes;
+            self.producers[producer].bytes += bytes;
+            self.corrected_write_latencies.record(corrected);
+            self.producers[producer].write_latencies.record(value);
+            let second = (sent - self.start_time).num_seconds().max(0) as usize;
+            while self.rate_series.len() <= second {
+                self.rate_series.push(RateSample { second: self.rate_series.len(), target: self.producer_rate, achieved: 0 });
+            }
+            self.rate_series[second].achieved += 1;
+            let bucket = self.bucket(sent);
+            bucket.messages_sent += 1;
+            bucket.bytes_sent    += bytes;
+            if let Some(latencies) = bucket.write_latencies.as_mut() {
+                latencies.record(value);
+            }
         }
     }
 
-    pub fn add_read_latency(&mut self, value: f64) {
+    pub fn add_read_latency(&mut self, consumer: usize, value: f64, received: DateTime<Utc>, bytes: u64) {
         if value >= 0.0 {
-            self.read_latencies.push(value);
+            self.read_latencies.record(value);
+            self.consumers[consumer].read_latencies.record(value);
+            self.consumers[consumer].bytes += bytes;
+            let bucket = self.bucket(received);
+            bucket.messages_read += 1;
+            bucket.bytes_read    += bytes;
+            if let Some(latencies) = bucket.read_latencies.as_mut() {
+                latencies.record(value);
+            }
+        }
+    }
+
+    /*
+     * Time-series interval the given instant of the run falls in, created on
+     * demand. Intervals that ended more than TIMESERIES_GRACE before it are
+     * closed, so a long run only keeps the histograms of its last intervals.
+     */
+    fn bucket(&mut self, at: DateTime<Utc>) -> &mut TimeSeriesBucket {
+        let elapsed = (at - self.start_time).num_milliseconds().max(0) as u64;
+        let index   = (elapsed / self.timeseries_interval) as usize;
+        while self.timeseries.len() <= index {
+            let start = (self.timeseries.len() as u64 * self.timeseries_interval) as f64 / 1000.0;
+            self.timeseries.push(TimeSeriesBucket::new(start));
+        }
+        while (self.timeseries_closed as u64 + 1) * self.timeseries_interval + TIMESERIES_GRACE <= elapsed {
+            self.timeseries[self.timeseries_closed].calculate_metrics();
+            self.timeseries_closed += 1;
+        }
+        &mut self.timeseries[index]
+    }
+
+    pub fn add_e2e_latency(&mut self, value: f64) {
+        if value >= 0.0 {
+            self.e2e_latencies.record(value);
         }
     }
 
@@ -89,43 +506,142 @@ impl TestResult {
         }
     }
 
-    pub fn calculate_metrics(&mut self) {
-        // Remove write failures
-        let latencies = self.write_latencies.clone();
-        self.write_latencies.clear();
-        for &latency in &latencies {
-            if latency > 0.0 {
-                self.write_latencies.push(latency);
-            }
+    pub fn set_producer_duration(&mut self, producer: usize, value: f64) {
+        if value > 0.0 {
+            self.producers[producer].duration = value;
+        }
+    }
+
+    pub fn set_consumer_duration(&mut self, consumer: usize, value: f64) {
+        if value > 0.0 {
+            self.consumers[consumer].duration = value;
+        }
+    }
+
+    pub fn add_verification(&mut self, value: VerificationResult) {
+        self.verification.push(value);
+    }
+
+    pub fn add_segment_count(&mut self, at: DateTime<Utc>, segments: usize) {
+        self.segment_timeline.push(SegmentCountSample {
+            time:      (at - self.start_time).num_milliseconds() as f64 / 1000.0,
+            timestamp: at.to_rfc3339(),
+            segments,
+        });
+    }
+
+    pub fn set_retention(&mut self, value: RetentionResult) {
+        self.retention = Some(value);
+    }
+
+    // Readers of a group read disjoint events, their segment counts add up.
+    pub fn add_segment_events(&mut self, subscription: usize, value: HashMap<String, u64>) {
+        let segments = &mut self.subscriptions[subscription].segments;
+        for (segment, events) in value {
+            *segments.entry(segment).or_insert(0) += events;
         }
+    }
+
+    pub fn add_subscription_lag(&mut self, subscription: usize, value: i64) {
+        self.subscriptions[subscription].lags.push(value);
+    }
+
+    pub fn calculate_metrics(&mut self) {
         self.message_num = self.write_latencies.len() as u32;
         // Calculate latency percentiles
-        let mut data = Data::new(self.write_latencies.clone());
-        self.write_latency_50pct = Self::round3(data.percentile(50));
-        self.write_latency_75pct = Self::round3(data.percentile(75));
-        self.write_latency_95pct = Self::round3(data.percentile(95));
-        self.write_latency_99pct = Self::round3(data.percentile(99));
-        self.write_latency_avg   = Self::round3(data.mean().unwrap());
-        self.write_latency_max   = Self::round3(data.max());
-        // Write Histogram
-        self.write_latencies.sort_by(|a, b| a.partial_cmp(b).unwrap());
-        for &latency in &self.write_latencies {
-            *self.write_latency_hist.entry(latency as u32).or_insert(0) += 1;
-        }
-        // Read Histogram
-        self.read_latencies.sort_by(|a, b| a.partial_cmp(b).unwrap());
-        for &latency in &self.read_latencies {
-            *self.read_latency_hist.entry(latency as u32).or_insert(0) += 1;
+        if !self.write_latencies.is_empty() {
+            let data = &self.write_latencies;
+            self.write_latency_50pct = Self::round3(data.percentile(50.0));
+            self.write_latency_75pct = Self::round3(data.percentile(75.0));
+            self.write_latency_95pct = Self::round3(data.percentile(95.0));
+            self.write_latency_99pct = Self::round3(data.percentile(99.0));
+            self.write_latency_avg   = Self::round3(data.mean());
+            self.write_latency_max   = Self::round3(data.max());
+        }
+        // Coordinated-omission corrected latency percentiles
+        if !self.corrected_write_latencies.is_empty() {
+            let data = &self.corrected_write_latencies;
+            self.corrected_write_latency_50pct = Self::round3(data.percentile(50.0));
+            self.corrected_write_latency_75pct = Self::round3(data.percentile(75.0));
+            self.corrected_write_latency_95pct = Self::round3(data.percentile(95.0));
+            self.corrected_write_latency_99pct = Self::round3(data.percentile(99.0));
+            self.corrected_write_latency_avg   = Self::round3(data.mean());
+            self.corrected_write_latency_max   = Self::round3(data.max());
+        }
+        // End-to-end latency percentiles
+        if !self.e2e_latencies.is_empty() {
+            let data = &self.e2e_latencies;
+            self.e2e_latency_50pct = Self::round3(data.percentile(50.0));
+            self.e2e_latency_75pct = Self::round3(data.percentile(75.0));
+            self.e2e_latency_95pct = Self::round3(data.percentile(95.0));
+            self.e2e_latency_99pct = Self::round3(data.percentile(99.0));
+            self.e2e_latency_avg   = Self::round3(data.mean());
+            self.e2e_latency_max   = Self::round3(data.max());
         }
+        // Sizes of the events actually sent
+        if !self.message_sizes.is_empty() {
+            let data = &self.message_sizes;
+            self.message_size_avg   = Self::round3(data.mean());
+            self.message_size_50pct = data.percentile(50.0);
+            self.message_size_95pct = data.percentile(95.0);
+            self.message_size_99pct = data.percentile(99.0);
+            self.message_size_max   = data.max();
+        }
+        // Serialized histograms and percentile distributions
+        self.write_latency_hist                   = self.write_latencies.serialize();
+        self.write_latency_distribution           = self.write_latencies.distribution();
+        self.corrected_write_latency_hist         = self.corrected_write_latencies.serialize();
+        self.corrected_write_latency_distribution = self.corrected_write_latencies.distribution();
+        self.read_latency_hist                    = self.read_latencies.serialize();
+        self.read_latency_distribution            = self.read_latencies.distribution();
+        self.e2e_latency_hist                     = self.e2e_latencies.serialize();
+        self.e2e_latency_distribution             = self.e2e_latencies.distribution();
         /*
          * Throughput = Total Output / Total Time (MB/s)
          * where:
-         *   Total Output = total MB sent
+         *   Total Output = total MB sent, summed over the actual event sizes
          *   Total Time   = total duration in seconds
          */
-        self.duration   = self.duration / 1000.0;
-        self.sent_data  = Self::calculate_data_sent(self.message_num.into(), self.message_size.into());
-        self.throughput = self.sent_data / self.duration;
+        self.duration  /= 1000.0;
+        self.sent_data  = Self::calculate_data_sent(self.sent_bytes);
+        if self.duration > 0.0 {
+            self.throughput = self.sent_data / self.duration;
+            // Messages per second actually achieved against the configured producer_rate
+            self.achieved_rate = Self::round3(self.message_num as f64 / self.duration);
+        }
+        for producer in &mut self.producers {
+            producer.calculate_metrics();
+        }
+        for consumer in &mut self.consumers {
+            consumer.calculate_metrics();
+        }
+        for subscription in &mut self.subscriptions {
+            subscription.calculate_metrics(&self.consumers);
+        }
+        for bucket in &mut self.timeseries {
+            bucket.calculate_metrics();
+        }
+        if self.read_mode == "catchup" {
+            self.calculate_catchup();
+        }
+    }
+
+    // Every reader group reads the whole backlog, the slowest one sets the time to catch up.
+    fn calculate_catchup(&mut self) {
+        let time_to_catch_up = self.subscriptions.iter().map(|subscription| subscription.duration).fold(0.0, f64::max);
+        let mut catchup = CatchupResult {
+            delay:            self.catchup_delay,
+            backlog_events:   self.message_num,
+            backlog_bytes:    self.sent_bytes,
+            time_to_catch_up: Self::round3(time_to_catch_up),
+            drain_rate:       0.0,
+            drain_throughput: 0.0,
+        };
+        if time_to_catch_up > 0.0 {
+            catchup.drain_rate       = Self::round3(self.message_num as f64 / time_to_catch_up);
+            catchup.drain_throughput = Self::calculate_data_sent(self.sent_bytes) / time_to_catch_up;
+        }
+        self.catchup = Some(catchup);
     }
 
     pub fn to_file(&self) -> std::io::Result<()> {
@@ -139,6 +655,121 @@ impl TestResult {
         file.write_all(json.as_bytes())?;
 
         println!("Results writen in {}", name);
+
+        if self.timeseries_csv {
+            let name = format!("result_{}.csv", formatted_date);
+            let mut file = File::create(name.clone())?;
+            writeln!(file, "start,messages_sent,messages_read,bytes_sent,bytes_read,write_latency_50pct,write_latency_95pct,write_latency_99pct,read_latency_50pct,read_latency_95pct,read_latency_99pct")?;
+            for bucket in &self.timeseries {
+                writeln!(
+                    file, "{},{},{},{},{},{},{},{},{},{},{}",
+                    bucket.start, bucket.messages_sent, bucket.messages_read, bucket.bytes_sent, bucket.bytes_read,
+                    bucket.write_latency_50pct, bucket.write_latency_95pct, bucket.write_latency_99pct,
+                    bucket.read_latency_50pct, bucket.read_latency_95pct, bucket.read_latency_99pct
+                )?;
+            }
+            println!("Time-series writen in {}", name);
+        }
         Ok(())
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(value: f64, expected: f64) -> bool {
+        (value - expected).abs() <= expected / 100.0
+    }
+
+    // Two producers write events of 1 MB for 2 s, the second one fails once.
+    #[test]
+    fn calculate_metrics_splits_the_run_per_producer() {
+        let mut conf = Config::new();
+        conf.producers     = 2;
+        conf.producer_rate = 2;
+        let mut result = TestResult::new(conf);
+        let start = Utc::now();
+        result.set_start_time(start);
+        for sent in [0, 500, 1200] {
+            result.add_write_latency(0, 2.0, 3.0, start + chrono::Duration::milliseconds(sent), 1_000_000);
+        }
+        result.add_write_latency(1, 4.0, 9.0, start + chrono::Duration::milliseconds(1500), 1_000_000);
+        result.add_write_latency(1, -1.0, -1.0, start, 1_000_000);
+        result.set_duration(2000.0);
+        result.set_producer_duration(0, 2000.0);
+        result.set_producer_duration(1, 2000.0);
+        result.calculate_metrics();
+
+        assert_eq!(result.message_num, 4);
+        assert_eq!(result.duration, 2.0);
+        assert_eq!(result.sent_bytes, 4_000_000);
+        assert_eq!(result.sent_data, 4.0);
+        assert_eq!(result.throughput, 2.0);
+        // Latencies come from the histograms, precise to three significant digits
+        assert!(close(result.write_latency_50pct, 2.0));
+        assert!(close(result.write_latency_max, 4.0));
+        assert!(close(result.corrected_write_latency_avg, 4.5));
+        assert!(close(result.corrected_write_latency_max, 9.0));
+        assert_eq!(result.achieved_rate, 2.0);
+        assert_eq!(result.rate_series.iter().map(|sample| (sample.target, sample.achieved)).collect::<Vec<_>>(), vec![(2, 2), (2, 2)]);
+        assert_eq!(result.producers[0].message_num, 3);
+        assert!(close(result.producers[0].write_latency_avg, 2.0));
+        assert_eq!(result.producers[0].throughput, 1.5);
+        assert_eq!(result.producers[1].message_num, 1);
+        assert!(close(result.producers[1].write_latency_max, 4.0));
+        assert_eq!(result.producers[1].throughput, 0.5);
+    }
+
+    // Events of two intervals of 500 ms, the last one only has a read.
+    #[test]
+    fn timeseries_buckets_follow_the_run() {
+        let mut conf = Config::new();
+        conf.timeseries_interval = 500;
+        let mut result = TestResult::new(conf);
+        let start = Utc::now();
+        result.set_start_time(start);
+        let at = |millis| start + chrono::Duration::milliseconds(millis);
+        result.add_write_latency(0, 1.0, 1.0, at(0), 1000);
+        result.add_write_latency(0, 1.0, 1.0, at(100), 1000);
+        result.add_write_latency(0, 8.0, 8.0, at(600), 1000);
+        result.add_read_latency(0, 0.5, at(700), 1000);
+        result.add_read_latency(0, 2.0, at(1200), 1000);
+        result.set_duration(1500.0);
+        result.calculate_metrics();
+
+        let buckets = &result.timeseries;
+        assert_eq!(buckets.iter().map(|bucket| bucket.start).collect::<Vec<_>>(), vec![0.0, 0.5, 1.0]);
+        assert_eq!((buckets[0].messages_sent, buckets[0].bytes_sent, buckets[0].messages_read), (2, 2000, 0));
+        assert!(close(buckets[0].write_latency_50pct, 1.0));
+        assert_eq!((buckets[1].messages_sent, buckets[1].messages_read), (1, 1));
+        assert!(close(buckets[1].write_latency_99pct, 8.0));
+        assert!(close(buckets[1].read_latency_50pct, 0.5));
+        assert_eq!((buckets[2].messages_sent, buckets[2].bytes_read), (0, 1000));
+        assert!(close(buckets[2].read_latency_99pct, 2.0));
+    }
+
+    // Intervals are closed 5 s after their end, later events only count in the totals.
+    #[test]
+    fn timeseries_buckets_drop_their_histograms_once_closed() {
+        let mut conf = Config::new();
+        conf.timeseries_interval = 500;
+        let mut result = TestResult::new(conf);
+        let start = Utc::now();
+        result.set_start_time(start);
+        let at = |millis| start + chrono::Duration::milliseconds(millis);
+        result.add_write_latency(0, 1.0, 1.0, at(0), 1000);
+        result.add_read_latency(0, 1.0, at(5499), 1000);
+        assert!(result.timeseries[0].write_latencies.is_some());
+        result.add_read_latency(0, 1.0, at(5500), 1000);
+        assert!(result.timeseries[0].write_latencies.is_none());
+        assert!(result.timeseries[1].write_latencies.is_some());
+        result.add_write_latency(0, 100.0, 100.0, at(400), 1000);
+        result.set_duration(6000.0);
+        result.calculate_metrics();
+
+        assert_eq!(result.timeseries.len(), 12);
+        assert_eq!(result.timeseries[0].messages_sent, 2);
+        assert!(close(result.timeseries[0].write_latency_99pct, 1.0));
+        assert!(result.timeseries.iter().all(|bucket| bucket.write_latencies.is_none() && bucket.read_latencies.is_none()));
+    }
+}
@@ -0,0 +1,68 @@
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+use serde::Serialize;
+use serde::Deserialize;
+use crate::config::Config;
+use crate::driver::Driver;
+
+// How often the stream head is polled while waiting for the retention to kick in.
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Serialize, Deserialize)]
+pub struct RetentionResult {
+    pub retention_type:      String,
+    // Seconds for time retention, bytes for size retention
+    pub retention_param:     i64,
+    pub expected_truncation: bool,
+    // None when the driver cannot tell whether the stream head was truncated
+    pub truncated:           Option<bool>,
+    pub passed:              Option<bool>,
+    // Seconds waited for the truncation
+    pub waited:              f64,
+}
+
+/*
+ * Checks after the run whether the head of the stream was truncated as the
+ * retention policy demands: once more data was written than `retention_size`,
+ * or the stream outlived `retention_time`. Retention is enforced in the
+ * background by the system, so an expected truncation is waited for up to
+ * `retention_check_timeout` seconds.
+ */
+pub fn check(conf: &Config, driver: &dyn Driver, written_bytes: u64, elapsed: Duration) -> RetentionResult {
+    let (retention_param, expected_truncation) = match conf.retention_type.as_str() {
+        "time" => (conf.retention_time, elapsed.as_secs() as i64 > conf.retention_time),
+        "size" => (conf.retention_size, written_bytes as i64 > conf.retention_size),
+        _      => (0, false),
+    };
+    println!("\t i Checking {} retention, truncation expected: {}", conf.retention_type, expected_truncation);
+
+    let start   = Instant::now();
+    let timeout = Duration::from_secs(conf.retention_check_timeout);
+    let truncated = loop {
+        match driver.head_truncated() {
+            Ok(Some(false)) if expected_truncation && start.elapsed() < timeout => thread::sleep(CHECK_INTERVAL),
+            Ok(truncated) => break truncated,
+            Err(e) => {
+                println!("\t i Error at checking the stream head: {}", e);
+                break None;
+            }
+        }
+    };
+    let passed = truncated.map(|truncated| truncated == expected_truncation);
+    match passed {
+        Some(true)  => println!("\t i Retention check passed"),
+        Some(false) if expected_truncation => println!("\t i Retention check failed: no truncation within {} s", conf.retention_check_timeout),
+        Some(false) => println!("\t i Retention check failed: stream head truncated"),
+        None        => println!("\t i Retention check not supported by driver {}", driver.name()),
+    }
+    RetentionResult {
+        retention_type: conf.retention_type.clone(),
+        retention_param,
+        expected_truncation,
+        truncated,
+        passed,
+        waited: (start.elapsed().as_secs_f64() * 1000.0).round() / 1000.0,
+    }
+}
+
@@ -0,0 +1,77 @@
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand::distributions::Distribution;
+use rand_distr::{Uniform, Zipf};
+
+enum Keys {
+    None,
+    Fixed,
+    Uniform(Uniform<u32>),
+    Zipf(Zipf<f64>),
+}
+
+/*
+ * Picks the routing key of every event. Keys are indices into `routing_key_count`
+ * keys named key-<index>; `none` writes without a routing key and lets the
+ * system place the events, `fixed` always uses key-0.
+ */
+pub struct KeySampler {
+    keys: Keys,
+    rng:  StdRng,
+}
+
+impl KeySampler {
+    pub fn new(mode: &str, count: u32, exponent: f64, seed: u64) -> Result<KeySampler, String> {
+        let keys = match mode {
+            "none"    => Keys::None,
+            "fixed"   => Keys::Fixed,
+            "uniform" | "zipf" if count == 0 => {
+                return Err("routing_key_count must be at least 1".to_string());
+            },
+            "uniform" => Keys::Uniform(Uniform::new(0, count)),
+            "zipf"    => Keys::Zipf(Zipf::new(count as u64, exponent).map_err(|e| format!("Invalid routing_key_zipf_exponent: {}", e))?),
+            other     => return Err(format!("Unknown routing_key mode '{}'", other)),
+        };
+        Ok(KeySampler { keys, rng: StdRng::seed_from_u64(seed) })
+    }
+
+    // Index of the routing key of the next event, None to write without one.
+    pub fn sample(&mut self) -> Option<u32> {
+        match &self.keys {
+            Keys::None             => None,
+            Keys::Fixed            => Some(0),
+            Keys::Uniform(uniform) => Some(uniform.sample(&mut self.rng)),
+            // Zipf draws ranks from 1, so key-0 is the hottest key
+            Keys::Zipf(zipf)       => Some(zipf.sample(&mut self.rng) as u32 - 1),
+        }
+    }
+}
+
+pub fn key_name(index: u32) -> String {
+    format!("key-{}", index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zipf_keys_stay_in_range_and_favour_key_0() {
+        let mut sampler = KeySampler::new("zipf", 10, 1.2, 1).unwrap();
+        let mut counts  = [0u32; 10];
+        for _ in 0..10_000 {
+            counts[sampler.sample().unwrap() as usize] += 1;
+        }
+        assert!(counts.iter().all(|&count| count > 0));
+        assert!(counts.windows(2).all(|pair| pair[0] > pair[1]), "{:?}", counts);
+    }
+
+    #[test]
+    fn modes_without_keys_or_count_are_handled() {
+        assert_eq!(KeySampler::new("none", 0, 1.0, 1).unwrap().sample(), None);
+        assert_eq!(KeySampler::new("fixed", 0, 1.0, 1).unwrap().sample(), Some(0));
+        assert!(KeySampler::new("uniform", 0, 1.0, 1).is_err());
+        assert!(KeySampler::new("zipf", 10, -1.0, 1).is_err());
+        assert!(KeySampler::new("random", 10, 1.0, 1).is_err());
+    }
+}
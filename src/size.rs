@@ -0,0 +1,199 @@
+use std::fs;
+use std::fmt;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand::distributions::Distribution;
+use rand_distr::{LogNormal, Normal, Uniform};
+use serde::{Deserialize, Serialize};
+
+// `message_size` in the configuration file, either a number of bytes or a distribution.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum MessageSize {
+    Bytes(u64),
+    Distribution(SizeDistribution),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "distribution", rename_all = "lowercase")]
+pub enum SizeDistribution {
+    Fixed     { size: u64 },
+    Uniform   { min: u64, max: u64 },
+    Normal    { mean: f64, stddev: f64 },
+    // mean and stddev of the sizes, not of the underlying normal distribution
+    Lognormal { mean: f64, stddev: f64 },
+    // file with one `size weight` pair per line
+    Empirical { file: String },
+}
+
+enum Sampler {
+    Fixed(u64),
+    Uniform(Uniform<u64>),
+    Normal(Normal<f64>),
+    LogNormal(LogNormal<f64>),
+    // Sizes with their cumulative weight
+    Empirical(Vec<(u64, f64)>),
+}
+
+/*
+ * Draws the size of every event from the configured distribution. Sizes are
+ * clamped to at least `min` bytes, so events keep room for the header.
+ */
+pub struct SizeSampler {
+    sampler: Sampler,
+    rng:     StdRng,
+    min:     u64,
+}
+
+impl SizeDistribution {
+    // Expected size of the events, used as the nominal message size.
+    pub fn mean(&self) -> Result<f64, String> {
+        Ok(match self {
+            SizeDistribution::Fixed { size }             => *size as f64,
+            SizeDistribution::Uniform { min, max }       => (*min + *max) as f64 / 2.0,
+            SizeDistribution::Normal { mean, .. }        => *mean,
+            SizeDistribution::Lognormal { mean, .. }     => *mean,
+            SizeDistribution::Empirical { file }         => {
+                let buckets = Self::load_empirical(file)?;
+                let total   = buckets.last().map(|bucket| bucket.1).unwrap_or(1.0);
+                let mut previous = 0.0;
+                buckets.iter().map(|&(size, cumulative)| {
+                    let weight = cumulative - previous;
+                    previous = cumulative;
+                    size as f64 * weight / total
+                }).sum()
+            },
+        })
+    }
+
+    fn load_empirical(file: &str) -> Result<Vec<(u64, f64)>, String> {
+        let content = fs::read_to_string(file).map_err(|e| format!("Could not read {}: {}", file, e))?;
+        let mut buckets    = Vec::new();
+        let mut cumulative = 0.0;
+        for line in content.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            let fields: Vec<&str> = line.split(|c: char| c == ',' || c.is_whitespace()).filter(|field| !field.is_empty()).collect();
+            let (size, weight) = match fields.as_slice() {
+                [size, weight] => (size.parse::<u64>(), weight.parse::<f64>()),
+                _ => return Err(format!("Invalid line in {}: '{}'", file, line)),
+            };
+            match (size, weight) {
+                (Ok(size), Ok(weight)) if weight >= 0.0 => {
+                    cumulative += weight;
+                    buckets.push((size, cumulative));
+                },
+                _ => return Err(format!("Invalid line in {}: '{}'", file, line)),
+            }
+        }
+        if cumulative <= 0.0 {
+            return Err(format!("No sizes with a positive weight in {}", file));
+        }
+        Ok(buckets)
+    }
+}
+
+impl fmt::Display for SizeDistribution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SizeDistribution::Fixed { size }             => write!(f, "{} bytes", size),
+            SizeDistribution::Uniform { min, max }       => write!(f, "uniform {}..{} bytes", min, max),
+            SizeDistribution::Normal { mean, stddev }    => write!(f, "normal mean {} stddev {} bytes", mean, stddev),
+            SizeDistribution::Lognormal { mean, stddev } => write!(f, "lognormal mean {} stddev {} bytes", mean, stddev),
+            SizeDistribution::Empirical { file }         => write!(f, "empirical {}", file),
+        }
+    }
+}
+
+impl SizeSampler {
+    pub fn new(distribution: &SizeDistribution, seed: u64, min: u64) -> Result<SizeSampler, String> {
+        let sampler = match distribution {
+            SizeDistribution::Fixed { size } => Sampler::Fixed(*size),
+            SizeDistribution::Uniform { min, max } => {
+                if min > max {
+                    return Err(format!("Uniform message_size min {} is above max {}", min, max));
+                }
+                Sampler::Uniform(Uniform::new_inclusive(*min, *max))
+            },
+            SizeDistribution::Normal { mean, stddev } => {
+                Sampler::Normal(Normal::new(*mean, *stddev).map_err(|e| format!("Invalid normal message_size: {}", e))?)
+            },
+            SizeDistribution::Lognormal { mean, stddev } => {
+                if *mean <= 0.0 {
+                    return Err("Log-normal message_size mean must be positive".to_string());
+                }
+                let sigma2 = (1.0 + (stddev * stddev) / (mean * mean)).ln();
+                let mu     = mean.ln() - sigma2 / 2.0;
+                Sampler::LogNormal(LogNormal::new(mu, sigma2.sqrt()).map_err(|e| format!("Invalid log-normal message_size: {}", e))?)
+            },
+            SizeDistribution::Empirical { file } => Sampler::Empirical(SizeDistribution::load_empirical(file)?),
+        };
+        Ok(SizeSampler { sampler, rng: StdRng::seed_from_u64(seed), min })
+    }
+
+    pub fn sample(&mut self) -> usize {
+        let size = match &self.sampler {
+            Sampler::Fixed(size)           => *size,
+            Sampler::Uniform(uniform)      => uniform.sample(&mut self.rng),
+            Sampler::Normal(normal)        => normal.sample(&mut self.rng).round().max(0.0) as u64,
+            Sampler::LogNormal(lognormal)  => lognormal.sample(&mut self.rng).round() as u64,
+            Sampler::Empirical(buckets)    => {
+                let total  = buckets.last().unwrap().1;
+                let target = Uniform::new(0.0, total).sample(&mut self.rng);
+                let index  = buckets.partition_point(|&(_, cumulative)| cumulative <= target);
+                buckets[index.min(buckets.len() - 1)].0
+            },
+        };
+        size.max(self.min) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn empirical_file(name: &str, content: &str) -> String {
+        let path = std::env::temp_dir().join(format!("sizes-{}-{}.txt", name, std::process::id()));
+        fs::write(&path, content).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn empirical_files_skip_zero_weight_sizes() {
+        let file = empirical_file("weights", "# size weight\n100 1\n200,0\n\n300 3\n");
+        let distribution = SizeDistribution::Empirical { file: file.clone() };
+        assert_eq!(SizeDistribution::load_empirical(&file).unwrap(), vec![(100, 1.0), (200, 1.0), (300, 4.0)]);
+        assert_eq!(distribution.mean().unwrap(), 250.0);
+        let mut sampler = SizeSampler::new(&distribution, 1, 0).unwrap();
+        let sizes: Vec<usize> = (0..1000).map(|_| sampler.sample()).collect();
+        assert!(sizes.iter().all(|&size| size == 100 || size == 300));
+        let large = sizes.iter().filter(|&&size| size == 300).count();
+        assert!((700..800).contains(&large), "large {}", large);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn invalid_empirical_files_are_rejected() {
+        for (name, content) in [("fields", "100 1 2\n"), ("negative", "100 -1\n"), ("empty", "100 0\n")] {
+            let file = empirical_file(name, content);
+            assert!(SizeDistribution::load_empirical(&file).is_err(), "{}", name);
+            fs::remove_file(file).unwrap();
+        }
+    }
+
+    #[test]
+    fn lognormal_sizes_keep_the_configured_mean_and_stddev() {
+        let distribution = SizeDistribution::Lognormal { mean: 1000.0, stddev: 500.0 };
+        let mut sampler  = SizeSampler::new(&distribution, 1, 0).unwrap();
+        let sizes: Vec<f64> = (0..100_000).map(|_| sampler.sample() as f64).collect();
+        let mean   = sizes.iter().sum::<f64>() / sizes.len() as f64;
+        let stddev = (sizes.iter().map(|size| (size - mean).powi(2)).sum::<f64>() / sizes.len() as f64).sqrt();
+        assert!((mean - 1000.0).abs() < 20.0, "mean {}", mean);
+        assert!((stddev - 500.0).abs() < 25.0, "stddev {}", stddev);
+    }
+
+    #[test]
+    fn sizes_are_clamped_to_the_minimum() {
+        let mut sampler = SizeSampler::new(&SizeDistribution::Fixed { size: 10 }, 1, 36).unwrap();
+        assert_eq!(sampler.sample(), 36);
+        assert!(SizeSampler::new(&SizeDistribution::Uniform { min: 5, max: 1 }, 1, 0).is_err());
+    }
+}
@@ -0,0 +1,193 @@
+use std::sync::Mutex;
+use serde::Serialize;
+use serde::Deserialize;
+use std::collections::HashMap;
+use crate::header::Header;
+use crate::header::NO_KEY;
+
+#[derive(Serialize, Deserialize)]
+pub struct VerificationResult {
+    pub subscription: usize,
+    pub name:         String,
+    pub events:       u64,
+    pub lost:         u64,
+    pub duplicates:   u64,
+    pub out_of_order: u64,
+    pub corrupted:    u64,
+    pub unverified:   u64,
+}
+
+// Sequence numbers seen from one writer, as a bitset.
+struct WriterTrack {
+    seen:     Vec<u64>,
+    distinct: u64,
+}
+
+struct VerifierState {
+    writers:      Vec<WriterTrack>,
+    /*
+     * Highest sequence seen per reader, writer and routing key. Order is only
+     * guaranteed per key, and only as seen by the reader that owns its segment;
+     * events without a routing key have no order to check.
+     */
+    highest:      HashMap<(usize, u32, u32), u64>,
+    events:       u64,
+    duplicates:   u64,
+    out_of_order: u64,
+    corrupted:    u64,
+    unverified:   u64,
+}
+
+/*
+ * Checks every event read by one reader group: the checksum, whether the
+ * sequence number of its writer was already seen, and whether its reader
+ * got it after a later event of the same writer and routing key. Events lost are
+ * the ones acknowledged to a writer but never read.
+ */
+pub struct Verifier {
+    state: Mutex<VerifierState>,
+}
+
+impl WriterTrack {
+    // Returns false if the sequence number was already seen.
+    fn insert(&mut self, sequence: u64) -> bool {
+        let (word, bit) = ((sequence / 64) as usize, sequence % 64);
+        if self.seen.len() <= word {
+            self.seen.resize(word + 1, 0);
+        }
+        if self.seen[word] & (1 << bit) != 0 {
+            return false;
+        }
+        self.seen[word] |= 1 << bit;
+        self.distinct   += 1;
+        true
+    }
+}
+
+impl Verifier {
+    pub fn new(writers: u32) -> Verifier {
+        Verifier {
+            state: Mutex::new(VerifierState {
+                writers:      (0..writers).map(|_| WriterTrack { seen: Vec::new(), distinct: 0 }).collect(),
+                highest:      HashMap::new(),
+                events:       0,
+                duplicates:   0,
+                out_of_order: 0,
+                corrupted:    0,
+                unverified:   0,
+            }),
+        }
+    }
+
+    pub fn check(&self, reader: usize, payload: &[u8]) {
+        let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        state.events += 1;
+        let header = match Header::parse(payload) {
+            Some(header) => header,
+            None => {
+                state.unverified += 1;
+                return;
+            }
+        };
+        let verification = match header.verification {
+            Some(verification) => verification,
+            None => {
+                state.unverified += 1;
+                return;
+            }
+        };
+        if verification.checksum != Header::checksum(payload) || verification.writer as usize >= state.writers.len() {
+            state.corrupted += 1;
+            return;
+        }
+        if !state.writers[verification.writer as usize].insert(header.sequence) {
+            state.duplicates += 1;
+            return;
+        }
+        if verification.key == NO_KEY {
+            return;
+        }
+        let highest = state.highest.entry((reader, verification.writer, verification.key)).or_insert(header.sequence);
+        if header.sequence < *highest {
+            state.out_of_order += 1;
+        } else {
+            *highest = header.sequence;
+        }
+    }
+
+    // `acked` holds the events acknowledged to every writer, warmup included.
+    pub fn result(&self, subscription: usize, name: String, acked: &[u64]) -> VerificationResult {
+        let state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let lost  = state.writers.iter()
+            .zip(acked)
+            .map(|(writer, &acked)| acked.saturating_sub(writer.distinct))
+            .sum();
+        VerificationResult {
+            subscription,
+            name,
+            events:       state.events,
+            lost,
+            duplicates:   state.duplicates,
+            out_of_order: state.out_of_order,
+            corrupted:    state.corrupted,
+            unverified:   state.unverified,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::prelude::Utc;
+
+    fn event(writer: u32, key: u32, sequence: u64) -> Vec<u8> {
+        let mut payload = vec![0u8; 64];
+        Header::new(sequence, Utc::now()).stamp_verified(writer, key, &mut payload);
+        payload
+    }
+
+    #[test]
+    fn counts_every_kind_of_error() {
+        let verifier = Verifier::new(2);
+        verifier.check(0, &event(0, 0, 0));
+        verifier.check(0, &event(0, 0, 2));
+        // Reordered after a later event of the same writer and key
+        verifier.check(0, &event(0, 0, 1));
+        verifier.check(0, &event(0, 0, 2));
+        let mut corrupted = event(1, 0, 0);
+        corrupted[40] ^= 1;
+        verifier.check(0, &corrupted);
+        verifier.check(0, &[0u8; 64]);
+
+        let result = verifier.result(0, "rg1".to_string(), &[4, 1]);
+        assert_eq!(result.events, 6);
+        assert_eq!(result.duplicates, 1);
+        assert_eq!(result.out_of_order, 1);
+        assert_eq!(result.corrupted, 1);
+        assert_eq!(result.unverified, 1);
+        // Sequence 3 of writer 0 and sequence 0 of writer 1 were never read intact
+        assert_eq!(result.lost, 2);
+    }
+
+    #[test]
+    fn order_is_checked_per_key_and_reader() {
+        let verifier = Verifier::new(1);
+        verifier.check(0, &event(0, 1, 5));
+        verifier.check(0, &event(0, 2, 4));
+        verifier.check(1, &event(0, 1, 3));
+        let result = verifier.result(0, "rg1".to_string(), &[3]);
+        assert_eq!(result.out_of_order, 0);
+    }
+
+    #[test]
+    fn unkeyed_events_are_not_ordered() {
+        let verifier = Verifier::new(1);
+        verifier.check(0, &event(0, NO_KEY, 1));
+        verifier.check(0, &event(0, NO_KEY, 0));
+        verifier.check(0, &event(0, NO_KEY, 0));
+        let result = verifier.result(0, "rg1".to_string(), &[2]);
+        assert_eq!(result.out_of_order, 0);
+        assert_eq!(result.duplicates, 1);
+        assert_eq!(result.lost, 0);
+    }
+}